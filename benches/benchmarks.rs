@@ -1,20 +1,192 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use csv::{ByteRecord, StringRecord};
+use rust_decimal::Decimal;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::File;
+use std::io::{BufWriter, Read};
 use std::path::PathBuf;
-use toy_payments_engine::run_transactions_from_file;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use toy_payments_engine::common::input_format::{InputFormat, TransactionReader};
+use toy_payments_engine::common::types::{ClientId, TransactionId, TransactionType};
+use toy_payments_engine::models::transaction::{ColumnMap, Transaction};
+use toy_payments_engine::support::generator::{GeneratorConfig, TransactionGenerator};
+use toy_payments_engine::{
+    convert_files_to_binary, run_transactions_from_file, run_transactions_from_file_with_options,
+    write_transactions, RunOptions, StoreKind,
+};
+
+// the disk history store has to keep the peak heap usage below this
+const DISK_HISTORY_PEAK_BYTES: usize = 128 * 1024 * 1024;
+
+/// Tracks the current and peak heap usage of the benchmark process.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// the parsing before rows were deserialized from a `ByteRecord`, fields read by position from
+// a validated `StringRecord`, kept here as the baseline for the `parse generated 1m` group
+fn parse_string_record(record: &StringRecord) -> Option<Transaction> {
+    let field = |index: usize| record.get(index).map(str::trim);
+    let tx_type = TransactionType::from_str(field(0)?).ok()?;
+    let client_id: ClientId = field(1)?.parse().ok()?;
+    let tx_id: TransactionId = field(2)?.parse().ok()?;
+    let amount = match tx_type {
+        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => None,
+        _ => Some(Decimal::from_str(field(3)?).ok()?.round_dp(4)),
+    };
+    Some(Transaction::new(tx_id, tx_type, client_id, amount))
+}
+
+fn bench_input(name: &str) -> PathBuf {
+    let mut input_path = PathBuf::from(file!());
+    input_path.pop();
+    input_path.push(name);
+    input_path
+}
+
+// the 100 MB input is kept in Git LFS, without it only the pointer file is checked out
+fn large_input() -> PathBuf {
+    let input_path = bench_input("transactions_100mb.csv");
+    let mut head = [0; 64];
+    let read = File::open(&input_path)
+        .and_then(|mut file| file.read(&mut head))
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", input_path.display(), err));
+    assert!(
+        !head[..read].starts_with(b"version https://git-lfs"),
+        "{} is a Git LFS pointer, fetch the file with `git lfs pull` first",
+        input_path.display()
+    );
+    input_path
+}
 
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("transactions 100mb", |b| {
-        let mut input_path = PathBuf::from(file!());
-        input_path.pop();
-        input_path.push("transactions_100mb.csv");
-        b.iter(|| run_transactions_from_file(&input_path))
+        let input_path = large_input();
+        b.iter(|| run_transactions_from_file(&input_path).unwrap())
+    });
+    c.bench_function("transactions 100mb (disk history)", |b| {
+        let input_path = large_input();
+        let options = RunOptions {
+            store: StoreKind::Disk(None),
+            ..RunOptions::default()
+        };
+
+        PEAK.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+        let baseline = ALLOCATED.load(Ordering::Relaxed);
+        run_transactions_from_file_with_options(&input_path, &options)
+            .expect("the 100 MB input runs with the disk history store");
+        let peak = PEAK.load(Ordering::Relaxed) - baseline;
+        assert!(
+            peak < DISK_HISTORY_PEAK_BYTES,
+            "peak heap usage of {} bytes exceeds {} bytes",
+            peak,
+            DISK_HISTORY_PEAK_BYTES
+        );
+
+        b.iter(|| run_transactions_from_file_with_options(&input_path, &options).unwrap())
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let generated_path = dir.path().join("generated.csv");
+    let config = GeneratorConfig {
+        clients: 10_000,
+        rows: 1_000_000,
+        ..GeneratorConfig::default()
+    };
+    let generator = TransactionGenerator::new(config.clone()).unwrap();
+    write_transactions(File::create(&generated_path).unwrap(), generator).unwrap();
+    let generated_bytes = std::fs::metadata(&generated_path).unwrap().len();
+
+    let mut group = c.benchmark_group("transactions generated 1m");
+    group.throughput(Throughput::Bytes(generated_bytes));
+    group.bench_function("run", |b| {
+        b.iter(|| run_transactions_from_file(&generated_path).unwrap())
+    });
+    group.finish();
+
+    // parsing only: a new `StringRecord` per row against the single buffer the engine reuses.
+    // On a single-core machine, the parsing with
+    //   cargo bench --bench benchmarks -- --warm-up-time 2 --measurement-time 30 \
+    //     --sample-size 20 "parse generated 1m"
+    // and the full run at the commits before and after rows were read from a `ByteRecord`
+    // (`transactions generated 1m`, renamed to `transactions generated 1m/run` by the change):
+    //
+    //                   string records   byte record buffer
+    //   parsing only    322 ms           288 ms
+    //   full run        1.01 s           1.00 s
+    //
+    // the parsing medians moved by up to 30% between runs, and one run had them the other way
+    let mut group = c.benchmark_group("parse generated 1m");
+    group.throughput(Throughput::Bytes(generated_bytes));
+    group.bench_function("string records", |b| {
+        b.iter(|| {
+            let mut reader = csv::Reader::from_path(&generated_path).unwrap();
+            for record in reader.records() {
+                black_box(parse_string_record(&record.unwrap()).unwrap());
+            }
+        })
     });
+    group.bench_function("byte record buffer", |b| {
+        b.iter(|| {
+            let mut reader = csv::Reader::from_path(&generated_path).unwrap();
+            let columns = ColumnMap::from_header(reader.headers().unwrap()).unwrap();
+            let mut record = ByteRecord::new();
+            while reader.read_byte_record(&mut record).unwrap() {
+                black_box(Transaction::from_byte_record(&record, &columns).unwrap());
+            }
+        })
+    });
+    group.finish();
+
+    // the same transactions as CSV and in the binary format, parsed and run through the engine
+    let binary_path = dir.path().join("generated.txbin");
+    let binary_file = BufWriter::new(File::create(&binary_path).unwrap());
+    convert_files_to_binary(&[&generated_path], &RunOptions::default(), binary_file).unwrap();
+
+    let mut group = c.benchmark_group("input format generated 1m");
+    group.throughput(Throughput::Elements(config.rows));
+    for (name, path, format) in [
+        ("csv", &generated_path, InputFormat::Csv),
+        ("binary", &binary_path, InputFormat::Binary),
+    ] {
+        group.bench_function(format!("{} parse", name), |b| {
+            b.iter(|| {
+                let mut reader = TransactionReader::open(path, format, b',').unwrap();
+                while let Some(row) = reader.next_row().unwrap() {
+                    black_box(row);
+                }
+            })
+        });
+        group.bench_function(format!("{} run", name), |b| {
+            b.iter(|| run_transactions_from_file(path))
+        });
+    }
+    group.finish();
 
     c.bench_function("transactions 250", |b| {
-        let mut input_path = PathBuf::from(file!());
-        input_path.pop();
-        input_path.push("transactions_250.csv");
-        b.iter(|| run_transactions_from_file(&input_path))
+        let input_path = bench_input("transactions_250.csv");
+        b.iter(|| run_transactions_from_file(&input_path).unwrap())
     });
 }
 
@@ -0,0 +1,64 @@
+//! Writes the seed corpus of the fuzz targets in `fuzz/` from the files in `tests/inputs`.
+//!
+//! Run it from the repository root with `cargo run --example fuzz_seed_corpus`.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use toy_payments_engine::common::codec::Codec;
+use toy_payments_engine::common::input_format::{InputFormat, InputRow, TransactionReader};
+
+const INPUTS: &str = "tests/inputs";
+const CORPUS: &str = "fuzz/corpus";
+// the larger fixtures only repeat the rows of these
+const ROW_SOURCES: [&str; 2] = ["simple", "full"];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut names = fs::read_dir(INPUTS)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    names.sort();
+
+    for path in names {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let format = InputFormat::from_path(&path);
+        let content = fs::read(&path)?;
+
+        // the file and record targets read CSV only
+        if format == InputFormat::Csv {
+            write_seed("ingest_file", &name, &content)?;
+
+            if ROW_SOURCES.contains(&name.as_ref()) {
+                for (line, row) in content.split(|byte| *byte == b'\n').enumerate() {
+                    if row.is_empty() {
+                        continue;
+                    }
+                    write_seed("parse_record", &format!("{}-{}", name, line + 1), row)?;
+                }
+            }
+        }
+
+        let mut encoded = Vec::new();
+        let mut reader = TransactionReader::new(content.as_slice(), format, b',')?;
+        while let Some(row) = reader.next_row()? {
+            match row {
+                InputRow::Transaction(_, transaction) => transaction.encode(&mut encoded),
+                InputRow::Invalid(line, err) => {
+                    return Err(format!("{}:{}: {}", path.display(), line, err).into())
+                }
+            }
+        }
+        if !encoded.is_empty() {
+            write_seed("manager_sequence", &name, &encoded)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_seed(target: &str, name: &str, content: &[u8]) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(CORPUS).join(target);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(name), content)?;
+    Ok(())
+}
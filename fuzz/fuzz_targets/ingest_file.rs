@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use toy_payments_engine::{run_transactions_from_reader, ErrorPolicy, RunOptions};
+
+fuzz_target!(|data: &[u8]| {
+    // everything that is optional is switched on to reach as much code as possible
+    let options = RunOptions {
+        record_events: true,
+        bookkeeping: true,
+        verify: true,
+        invalid_rows: ErrorPolicy::Skip,
+        ..RunOptions::default()
+    };
+
+    let _ = run_transactions_from_reader(data, &options);
+});
@@ -0,0 +1,33 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use toy_payments_engine::common::codec::Codec;
+use toy_payments_engine::models::transaction::Transaction;
+use toy_payments_engine::support::differential::first_divergence;
+use toy_payments_engine::TransactionManager;
+
+// the input is a sequence of transactions in the store encoding, see `common::codec`
+fuzz_target!(|data: &[u8]| {
+    let mut bytes = data;
+    let mut transactions = Vec::new();
+    while let Some(tx) = Transaction::decode(&mut bytes) {
+        transactions.push(tx);
+    }
+
+    let mut manager = TransactionManager::new().with_journal();
+    for tx in transactions.iter().cloned() {
+        let client_id = tx.get_client_id();
+        let before = manager.get_snapshot(client_id).unwrap();
+
+        // a rejected transaction must not touch an existing account
+        if manager.add_transaction(tx).is_err() && before.is_some() {
+            assert_eq!(manager.get_snapshot(client_id).unwrap(), before);
+        }
+    }
+    let _ = manager.audit().unwrap();
+    let _ = manager.get_all_values().unwrap();
+
+    if let Some(divergence) = first_divergence(TransactionManager::new(), transactions).unwrap() {
+        panic!("{}", divergence);
+    }
+});
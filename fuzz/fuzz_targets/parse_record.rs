@@ -0,0 +1,27 @@
+#![no_main]
+
+use csv::{ByteRecord, StringRecord};
+use libfuzzer_sys::fuzz_target;
+use toy_payments_engine::models::transaction::{ColumnMap, Transaction};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let columns = ColumnMap::default();
+    let mut record = ByteRecord::new();
+
+    while let Ok(true) = reader.read_byte_record(&mut record) {
+        let from_bytes = Transaction::from_byte_record(&record, &columns);
+
+        // both ways of reading a row have to agree on the rows the positional layout allows
+        if let Ok(string_record) = StringRecord::from_byte_record(record.clone()) {
+            let is_positional = string_record.len() == 4 || string_record.len() == 5;
+            let from_string = Transaction::try_from(string_record);
+            if is_positional {
+                assert_eq!(from_bytes.ok(), from_string.ok());
+            }
+        }
+    }
+});
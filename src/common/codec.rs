@@ -0,0 +1,242 @@
+use crate::common::types::{OpaqueId, TransactionType};
+use crate::models::transaction::Transaction;
+use crate::stores::transaction_store::{DisputeState, TransactionRecord};
+use rust_decimal::Decimal;
+
+/// Compact binary encoding used by the on-disk stores.
+pub trait Codec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from the front of `bytes` and advances past it.
+    fn decode(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
+
+macro_rules! impl_codec_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl Codec for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &mut &[u8]) -> Option<Self> {
+                    let head = take(bytes, std::mem::size_of::<$t>())?;
+                    Some(<$t>::from_le_bytes(head.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_int!(u8, u16, u32, u64, i128);
+
+impl Codec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        match u8::decode(bytes)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for OpaqueId {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let value = self.as_str();
+        out.push(value.len() as u8);
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let len = u8::decode(bytes)?;
+        let head = take(bytes, len as usize)?;
+        std::str::from_utf8(head).ok()?.parse().ok()
+    }
+}
+
+impl Codec for Decimal {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.serialize());
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let head: [u8; 16] = take(bytes, 16)?.try_into().ok()?;
+        // the flags hold only the scale and the sign, and the scale can be at most 28
+        if head[0] != 0 || head[1] != 0 || head[2] > 28 || head[3] & 0x7f != 0 {
+            return None;
+        }
+        Some(Decimal::deserialize(head))
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+            None => out.push(0),
+        }
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        match u8::decode(bytes)? {
+            0 => Some(None),
+            1 => Some(Some(T::decode(bytes)?)),
+            _ => None,
+        }
+    }
+}
+
+impl<A: Codec, B: Codec> Codec for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        Some((A::decode(bytes)?, B::decode(bytes)?))
+    }
+}
+
+impl Codec for TransactionType {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            TransactionType::Deposit => 0,
+            TransactionType::Withdrawal => 1,
+            TransactionType::Dispute => 2,
+            TransactionType::Resolve => 3,
+            TransactionType::Chargeback => 4,
+        };
+        out.push(tag);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        match u8::decode(bytes)? {
+            0 => Some(TransactionType::Deposit),
+            1 => Some(TransactionType::Withdrawal),
+            2 => Some(TransactionType::Dispute),
+            3 => Some(TransactionType::Resolve),
+            4 => Some(TransactionType::Chargeback),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for Transaction {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.get_transaction_id().encode(out);
+        self.get_transaction_type().encode(out);
+        self.get_client_id().encode(out);
+        self.get_amount().encode(out);
+        self.get_timestamp().encode(out);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let tx_id = Codec::decode(bytes)?;
+        let tx_type = Codec::decode(bytes)?;
+        let client_id = Codec::decode(bytes)?;
+        let amount = Codec::decode(bytes)?;
+        let timestamp: Option<u64> = Codec::decode(bytes)?;
+
+        let transaction = Transaction::new(tx_id, tx_type, client_id, amount);
+        Some(match timestamp {
+            Some(timestamp) => transaction.with_timestamp(timestamp),
+            None => transaction,
+        })
+    }
+}
+
+impl Codec for DisputeState {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            DisputeState::Undisputed => 0,
+            DisputeState::Disputed => 1,
+            DisputeState::ChargedBack => 2,
+        };
+        tag.encode(out);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        match u8::decode(bytes)? {
+            0 => Some(DisputeState::Undisputed),
+            1 => Some(DisputeState::Disputed),
+            2 => Some(DisputeState::ChargedBack),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for TransactionRecord {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.get_transaction().encode(out);
+        self.get_dispute_state().encode(out);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let mut record = TransactionRecord::new(Transaction::decode(bytes)?);
+        record.set_dispute_state(DisputeState::decode(bytes)?);
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::tx_id;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_transaction_round_trip() {
+        let transactions = vec![
+            Transaction::new(tx_id(1), TransactionType::Deposit, 2, Some(dec!(1.2345))),
+            Transaction::new(tx_id(3), TransactionType::Dispute, 4, None)
+                .with_timestamp(1700000000),
+        ];
+
+        for transaction in transactions {
+            let mut bytes = Vec::new();
+            transaction.encode(&mut bytes);
+
+            let mut input = bytes.as_slice();
+            let decoded = Transaction::decode(&mut input);
+
+            assert_eq!(decoded, Some(transaction));
+            assert!(input.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let transaction = Transaction::new(tx_id(1), TransactionType::Deposit, 2, Some(dec!(1.0)));
+        let mut bytes = Vec::new();
+        transaction.encode(&mut bytes);
+
+        let mut input = &bytes[..bytes.len() - 1];
+
+        assert_eq!(Transaction::decode(&mut input), None);
+    }
+
+    #[test]
+    fn test_invalid_decimal_scale() {
+        let mut bytes = Vec::new();
+        dec!(1.5).encode(&mut bytes);
+        bytes[2] = 29;
+
+        assert_eq!(Decimal::decode(&mut bytes.as_slice()), None);
+    }
+}
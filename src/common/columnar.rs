@@ -0,0 +1,321 @@
+use crate::common::input_format::InputRow;
+use crate::common::wire::WireId;
+use crate::managers::output_manager::OutputError;
+use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::transaction::{ColumnMap, Transaction};
+use crate::AppError;
+use arrow_array::{
+    ArrayRef, BooleanArray, Decimal128Array, RecordBatch, RecordBatchReader, UInt64Array,
+};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use arrow_schema::{DataType, Field, Schema};
+use csv::StringRecord;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::ChunkReader;
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Seek, Write};
+use std::sync::Arc;
+
+// an Arrow IPC file starts with this; a stream does not
+const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+
+/// The scale of the balance columns written to Parquet, the precision of the engine.
+pub const ACCOUNT_SCALE: u32 = 4;
+// wide enough for any rust_decimal value at that scale
+const ACCOUNT_PRECISION: u8 = 38;
+
+/**
+ * BatchReader reads transactions from the record batches of a Parquet or Arrow IPC file. The
+ * columns are named like the CSV header, and every value is validated the way a CSV field is,
+ * so a decimal, integer or string amount all work.
+ */
+pub struct BatchReader {
+    batches: Box<dyn RecordBatchReader>,
+    columns: ColumnMap,
+    // the rows of the current batch that have not been read yet
+    rows: VecDeque<StringRecord>,
+    number: u64,
+}
+
+impl BatchReader {
+    pub fn new(batches: Box<dyn RecordBatchReader>) -> Result<Self, AppError> {
+        let schema = batches.schema();
+        let header: StringRecord = schema.fields().iter().map(|field| field.name()).collect();
+        let columns = ColumnMap::from_header(&header).map_err(AppError::InvalidHeader)?;
+
+        Ok(BatchReader {
+            batches,
+            columns,
+            rows: VecDeque::new(),
+            number: 0,
+        })
+    }
+
+    /// The next row, numbered from 1, or `None` after the last batch.
+    pub fn next_row(&mut self) -> Result<Option<InputRow>, AppError> {
+        loop {
+            if let Some(record) = self.rows.pop_front() {
+                self.number += 1;
+                return Ok(Some(
+                    match Transaction::from_record(&record, &self.columns) {
+                        Ok(transaction) => InputRow::Transaction(self.number, transaction),
+                        Err(err) => InputRow::Invalid(self.number, err.to_string()),
+                    },
+                ));
+            }
+
+            // only one batch is held in memory at a time
+            match self.batches.next() {
+                Some(batch) => self.rows = batch_records(&batch.map_err(columnar_error)?)?,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Reads a Parquet file batch by batch.
+pub fn read_parquet<R: ChunkReader + 'static>(input: R) -> Result<BatchReader, AppError> {
+    let batches = ParquetRecordBatchReaderBuilder::try_new(input)
+        .and_then(|builder| builder.build())
+        .map_err(columnar_error)?;
+    BatchReader::new(Box::new(batches))
+}
+
+/// Reads an Arrow IPC file or stream batch by batch. Only the file format needs to seek.
+pub fn read_arrow_ipc<R: Read + Seek + 'static>(input: R) -> Result<BatchReader, AppError> {
+    let mut input = BufReader::new(input);
+    let batches: Box<dyn RecordBatchReader> = match input.fill_buf()?.starts_with(ARROW_FILE_MAGIC)
+    {
+        true => Box::new(FileReader::try_new(input, None).map_err(columnar_error)?),
+        false => Box::new(StreamReader::try_new(input, None).map_err(columnar_error)?),
+    };
+    BatchReader::new(batches)
+}
+
+// every value of a batch as text, with nulls as empty fields
+fn batch_records(batch: &RecordBatch) -> Result<VecDeque<StringRecord>, AppError> {
+    use std::fmt::Write as _;
+
+    let options = FormatOptions::new().with_null("");
+    let formatters = batch
+        .columns()
+        .iter()
+        .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(columnar_error)?;
+
+    let mut field = String::new();
+    let mut records = VecDeque::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        let mut record = StringRecord::with_capacity(0, formatters.len());
+        for formatter in formatters.iter() {
+            field.clear();
+            write!(field, "{}", formatter.value(row)).map_err(columnar_error)?;
+            record.push_field(&field);
+        }
+        records.push_back(record);
+    }
+    Ok(records)
+}
+
+/// Writes the accounts as one Parquet row group, with the balances as decimals of scale 4.
+pub fn write_accounts_parquet<W: Write + Send>(
+    writer: W,
+    clients: &[ClientSnapshot],
+) -> Result<(), OutputError> {
+    let balance = |name: &str| {
+        Field::new(
+            name,
+            DataType::Decimal128(ACCOUNT_PRECISION, ACCOUNT_SCALE as i8),
+            false,
+        )
+    };
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("client", DataType::UInt64, false),
+        balance("available"),
+        balance("held"),
+        balance("total"),
+        Field::new("locked", DataType::Boolean, false),
+    ]));
+
+    let decimals = |value: fn(&ClientSnapshot) -> Decimal| -> Result<ArrayRef, OutputError> {
+        let array = clients
+            .iter()
+            .map(|client| decimal_at_scale(value(client)))
+            .collect::<Decimal128Array>()
+            .with_precision_and_scale(ACCOUNT_PRECISION, ACCOUNT_SCALE as i8)?;
+        Ok(Arc::new(array))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            clients
+                .iter()
+                .map(|client| client.get_id().to_wire())
+                .collect::<UInt64Array>(),
+        ),
+        decimals(ClientSnapshot::get_available)?,
+        decimals(ClientSnapshot::get_held)?,
+        decimals(ClientSnapshot::get_total)?,
+        Arc::new(
+            clients
+                .iter()
+                .map(|client| Some(client.get_locked()))
+                .collect::<BooleanArray>(),
+        ),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, schema, Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+// the unscaled value at `ACCOUNT_SCALE`; balances never carry more decimal places
+fn decimal_at_scale(value: Decimal) -> i128 {
+    let value = value.round_dp(ACCOUNT_SCALE);
+    value.mantissa() * 10_i128.pow(ACCOUNT_SCALE - value.scale())
+}
+
+fn columnar_error<E: std::fmt::Display>(err: E) -> AppError {
+    AppError::ColumnarReadError(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, StringArray, UInt16Array, UInt32Array};
+    use arrow_ipc::writer::{FileWriter, StreamWriter};
+    use rstest::rstest;
+    use std::io::Cursor;
+
+    fn batch() -> RecordBatch {
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            (
+                "Type",
+                Arc::new(StringArray::from(vec!["deposit", "dispute", "withdrawal"])),
+            ),
+            ("client", Arc::new(UInt16Array::from(vec![1, 1, 2]))),
+            ("tx", Arc::new(UInt32Array::from(vec![1, 1, 2]))),
+            (
+                "amount",
+                Arc::new(
+                    Decimal128Array::from(vec![Some(15_000), None, Some(-1)])
+                        .with_precision_and_scale(38, 4)
+                        .unwrap(),
+                ),
+            ),
+            ("note", Arc::new(StringArray::from(vec!["a", "b", "c"]))),
+        ];
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    fn parquet_file() -> Vec<u8> {
+        let batch = batch();
+        let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    fn ipc_file() -> Vec<u8> {
+        let batch = batch();
+        let mut writer = FileWriter::try_new(Vec::new(), &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    fn ipc_stream() -> Vec<u8> {
+        let batch = batch();
+        let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    #[rstest]
+    #[case("parquet")]
+    #[case("ipc file")]
+    #[case("ipc stream")]
+    fn test_read_batches(#[case] format: &str) {
+        // Arrange
+        let mut reader = match format {
+            "parquet" => read_parquet(bytes::Bytes::from(parquet_file())).unwrap(),
+            "ipc file" => read_arrow_ipc(Cursor::new(ipc_file())).unwrap(),
+            _ => read_arrow_ipc(Cursor::new(ipc_stream())).unwrap(),
+        };
+
+        // Act
+        let mut rows = Vec::new();
+        while let Some(row) = reader.next_row().unwrap() {
+            rows.push(row);
+        }
+
+        // Assert
+        assert_eq!(rows.len(), 3);
+        match &rows[0] {
+            InputRow::Transaction(line, transaction) => {
+                assert_eq!(*line, 1);
+                assert_eq!(transaction.get_amount().unwrap().to_string(), "1.5000");
+                assert_eq!(transaction.get_metadata()[0].1, "a");
+            }
+            InputRow::Invalid(_, message) => panic!("unexpected invalid row: {}", message),
+        }
+        assert!(matches!(rows[1], InputRow::Transaction(2, _)));
+        assert!(matches!(rows[2], InputRow::Transaction(3, _)));
+    }
+
+    #[test]
+    fn test_missing_column() {
+        // Arrange
+        let batch = batch().project(&[0, 1, 3]).unwrap();
+        let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        let file = writer.into_inner().unwrap();
+
+        // Act
+        let result = read_parquet(bytes::Bytes::from(file));
+
+        // Assert
+        assert!(matches!(result, Err(AppError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn test_write_accounts_parquet() {
+        // Arrange
+        let clients = vec![
+            ClientSnapshot::new(1, Decimal::new(15, 1), Decimal::ZERO, false),
+            ClientSnapshot::new(2, Decimal::new(-12345, 4), Decimal::new(2, 0), true),
+        ];
+        let mut file = Vec::new();
+
+        // Act
+        write_accounts_parquet(&mut file, &clients).unwrap();
+
+        // Assert
+        let batches: Vec<RecordBatch> =
+            ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(file))
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        let batch = &batches[0];
+        let total = batch
+            .column_by_name("total")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Decimal128Array>()
+            .unwrap();
+        assert_eq!(total.data_type(), &DataType::Decimal128(38, 4));
+        assert_eq!(total.value_as_string(0), "1.5000");
+        assert_eq!(total.value_as_string(1), "0.7655");
+        assert_eq!(batch.num_rows(), 2);
+    }
+}
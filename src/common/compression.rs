@@ -0,0 +1,178 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Tells the compression from the leading bytes of a file, if they are a known magic number.
+    pub fn from_magic(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if head.starts_with(&ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Tells the compression from a `.gz` or `.zst` extension.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Opens a file for reading and decompresses it on the fly. The magic number decides; the
+/// extension is only used when the file starts with neither.
+pub fn open_input<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let head = reader.fill_buf()?;
+    let compression = Compression::from_magic(head)
+        .or_else(|| Compression::from_extension(path).filter(|_| !head.is_empty()))
+        .unwrap_or_default();
+
+    decompress(reader, compression)
+}
+
+pub fn decompress<R: BufRead + 'static>(
+    reader: R,
+    compression: Compression,
+) -> io::Result<Box<dyn Read>> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        // concatenated gzip members are read as one stream
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+    })
+}
+
+/**
+ * CompressedWriter compresses everything written to it. `finish` has to be called to write
+ * the end of the compressed stream.
+ */
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => CompressedWriter::Plain(writer),
+            Compression::Gzip => {
+                CompressedWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            Compression::Zstd => {
+                CompressedWriter::Zstd(zstd::stream::write::Encoder::new(writer, 0)?)
+            }
+        })
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            CompressedWriter::Plain(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+            CompressedWriter::Gzip(encoder) => encoder.finish(),
+            CompressedWriter::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Compression::None)]
+    #[case(Compression::Gzip)]
+    #[case(Compression::Zstd)]
+    fn test_round_trip(#[case] compression: Compression) {
+        let content = b"type,client,tx,amount\ndeposit,1,1,1.0\n";
+        let mut writer = CompressedWriter::new(Vec::new(), compression).unwrap();
+        writer.write_all(content).unwrap();
+        let compressed = writer.finish().unwrap();
+
+        let detected = Compression::from_magic(&compressed).unwrap_or_default();
+        let mut decompressed = Vec::new();
+        decompress(io::Cursor::new(compressed), detected)
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+
+        assert_eq!(detected, compression);
+        assert_eq!(decompressed, content);
+    }
+
+    #[test]
+    fn test_from_extension() {
+        let gzip = Compression::from_extension(Path::new("day.csv.GZ"));
+        let zstd = Compression::from_extension(Path::new("day.csv.zst"));
+        let plain = Compression::from_extension(Path::new("day.csv"));
+
+        assert_eq!(gzip, Some(Compression::Gzip));
+        assert_eq!(zstd, Some(Compression::Zstd));
+        assert_eq!(plain, None);
+    }
+}
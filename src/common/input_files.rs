@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum InputError {
+    #[error("failed to read {0}: {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("invalid pattern {0}: {1}")]
+    Pattern(String, glob::PatternError),
+
+    #[error("no input file matches {0}")]
+    NoMatch(String),
+}
+
+/// Turns the inputs given on the command line into the files to read, in the order they are
+/// read. A directory stands for the files in it and a glob for the files it matches, both in
+/// lexical order; other paths are kept as they are. A file named twice is only read once.
+pub fn expand_inputs<S: AsRef<str>>(inputs: &[S]) -> Result<Vec<PathBuf>, InputError> {
+    let mut files = Vec::new();
+    for input in inputs.iter() {
+        let input = input.as_ref();
+        let path = Path::new(input);
+        if path.is_dir() {
+            files.extend(directory_files(path)?);
+        } else if is_pattern(input) {
+            files.extend(glob_files(input)?);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.clone()));
+    Ok(files)
+}
+
+/// Reads a manifest listing one input per line, in the order they have to be read. Relative
+/// paths are relative to the manifest; blank lines and lines starting with `#` are ignored.
+pub fn read_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, InputError> {
+    let path = path.as_ref();
+    let content =
+        fs::read_to_string(path).map_err(|err| InputError::Io(path.to_path_buf(), err))?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base.join(line))
+        .collect())
+}
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn directory_files(path: &Path) -> Result<Vec<PathBuf>, InputError> {
+    let entries = fs::read_dir(path).map_err(|err| InputError::Io(path.to_path_buf(), err))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|err| InputError::Io(path.to_path_buf(), err))?;
+        // hidden files are left out, the way a shell glob would
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !hidden && entry.path().is_file() {
+            files.push(entry.path());
+        }
+    }
+    if files.is_empty() {
+        return Err(InputError::NoMatch(path.display().to_string()));
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn glob_files(pattern: &str) -> Result<Vec<PathBuf>, InputError> {
+    // like a shell, a wildcard does not match the leading dot of a hidden file
+    let options = glob::MatchOptions {
+        require_literal_leading_dot: true,
+        ..glob::MatchOptions::new()
+    };
+    let paths = glob::glob_with(pattern, options)
+        .map_err(|err| InputError::Pattern(pattern.to_string(), err))?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|err| InputError::Io(err.path().to_path_buf(), err.into()))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        return Err(InputError::NoMatch(pattern.to_string()));
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn fixture_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.csv", "a.csv", "c.txt", ".hidden.csv"] {
+            fs::write(dir.path().join(name), "type,client,tx,amount\n").unwrap();
+        }
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        dir
+    }
+
+    #[rstest]
+    #[case("", vec!["a.csv", "b.csv", "c.txt"])]
+    #[case("*.csv", vec!["a.csv", "b.csv"])]
+    #[case("b.csv", vec!["b.csv"])]
+    fn test_expand_inputs(#[case] input: &str, #[case] expected: Vec<&str>) {
+        let dir = fixture_dir();
+        let input = dir.path().join(input).to_string_lossy().into_owned();
+
+        let files = expand_inputs(&[input]).unwrap();
+
+        let expected: Vec<PathBuf> = expected.iter().map(|name| dir.path().join(name)).collect();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn test_expand_inputs_keeps_argument_order() {
+        let dir = fixture_dir();
+        let b = dir.path().join("b.csv").to_string_lossy().into_owned();
+        let all = dir.path().join("*.csv").to_string_lossy().into_owned();
+
+        let files = expand_inputs(&[b, all]).unwrap();
+
+        let expected = vec![dir.path().join("b.csv"), dir.path().join("a.csv")];
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn test_expand_inputs_without_match() {
+        let dir = fixture_dir();
+        let pattern = dir.path().join("*.json").to_string_lossy().into_owned();
+
+        let result = expand_inputs(&[pattern]);
+
+        assert!(matches!(result, Err(InputError::NoMatch(_))));
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let dir = fixture_dir();
+        let manifest = dir.path().join("order.txt");
+        fs::write(&manifest, "# oldest first\nb.csv\n\n  a.csv  \n").unwrap();
+
+        let files = read_manifest(&manifest).unwrap();
+
+        let expected = vec![dir.path().join("b.csv"), dir.path().join("a.csv")];
+        assert_eq!(files, expected);
+    }
+}
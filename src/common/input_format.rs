@@ -0,0 +1,330 @@
+#[cfg(feature = "parquet")]
+use crate::common::columnar::{read_arrow_ipc, read_parquet, BatchReader};
+use crate::common::compression::{open_input, Compression};
+use crate::common::wire::{decode_record, read_full, read_header, RECORD_LEN};
+use crate::models::transaction::{ColumnMap, Transaction};
+use crate::AppError;
+use csv::ByteRecord;
+#[cfg(feature = "parquet")]
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    JsonLines,
+    /// The fixed-width records of `common::wire`.
+    Binary,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// The Arrow IPC file or stream format.
+    #[cfg(feature = "parquet")]
+    ArrowIpc,
+}
+
+impl InputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InputFormat::Csv => "csv",
+            InputFormat::JsonLines => "jsonl",
+            InputFormat::Binary => "binary",
+            #[cfg(feature = "parquet")]
+            InputFormat::Parquet => "parquet",
+            #[cfg(feature = "parquet")]
+            InputFormat::ArrowIpc => "arrow",
+        }
+    }
+
+    /// Tells the format from a `.jsonl`, `.ndjson` or `.txbin` extension, looking past a
+    /// compression extension, or from `.parquet` and `.arrow` with the `parquet` feature.
+    /// Anything else is read as CSV.
+    pub fn from_path(path: &Path) -> Self {
+        let path = match Compression::from_extension(path) {
+            Some(_) => Path::new(path.file_stem().unwrap_or_default()),
+            None => path,
+        };
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("jsonl" | "ndjson") => InputFormat::JsonLines,
+            Some("txbin") => InputFormat::Binary,
+            #[cfg(feature = "parquet")]
+            Some("parquet") => InputFormat::Parquet,
+            #[cfg(feature = "parquet")]
+            Some("arrow" | "arrows" | "ipc" | "feather") => InputFormat::ArrowIpc,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" | "ndjson" => Ok(InputFormat::JsonLines),
+            "binary" => Ok(InputFormat::Binary),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(InputFormat::Parquet),
+            #[cfg(feature = "parquet")]
+            "arrow" => Ok(InputFormat::ArrowIpc),
+            _ => Err(()),
+        }
+    }
+}
+
+/// One row read from an input: a transaction or the reason it could not be read, with the line
+/// it was on.
+pub enum InputRow {
+    Transaction(u64, Transaction),
+    Invalid(u64, String),
+}
+
+/**
+ * TransactionReader reads the rows of one input in any of the formats. Rows that cannot be read
+ * are returned as `InputRow::Invalid`; only a failing reader is an error.
+ */
+pub enum TransactionReader<R: Read> {
+    Csv {
+        reader: csv::Reader<R>,
+        columns: ColumnMap,
+        record: ByteRecord,
+    },
+    JsonLines {
+        reader: BufReader<R>,
+        line: Vec<u8>,
+        number: u64,
+    },
+    Binary {
+        reader: BufReader<R>,
+        record: [u8; RECORD_LEN],
+        number: u64,
+    },
+    #[cfg(feature = "parquet")]
+    Batches(BatchReader),
+}
+
+impl TransactionReader<Box<dyn Read>> {
+    /// Opens a file. Text formats are decompressed while they are read; Parquet and Arrow IPC
+    /// files are read in place since they compress their own columns.
+    pub fn open(path: &Path, format: InputFormat, delimiter: u8) -> Result<Self, AppError> {
+        match format {
+            #[cfg(feature = "parquet")]
+            InputFormat::Parquet => {
+                Ok(TransactionReader::Batches(read_parquet(File::open(path)?)?))
+            }
+            #[cfg(feature = "parquet")]
+            InputFormat::ArrowIpc => Ok(TransactionReader::Batches(read_arrow_ipc(File::open(
+                path,
+            )?)?)),
+            _ => TransactionReader::new(open_input(path)?, format, delimiter),
+        }
+    }
+}
+
+impl<R: Read> TransactionReader<R> {
+    /// Opens an input; for CSV this reads and maps the header. Parquet and Arrow IPC input is
+    /// read into memory first, since a plain reader cannot seek; `open` does not need to.
+    pub fn new(input: R, format: InputFormat, delimiter: u8) -> Result<Self, AppError> {
+        match format {
+            #[cfg(feature = "parquet")]
+            InputFormat::Parquet => {
+                let content = read_all(input)?;
+                Ok(TransactionReader::Batches(read_parquet(
+                    bytes::Bytes::from(content),
+                )?))
+            }
+            #[cfg(feature = "parquet")]
+            InputFormat::ArrowIpc => {
+                let content = read_all(input)?;
+                Ok(TransactionReader::Batches(read_arrow_ipc(
+                    std::io::Cursor::new(content),
+                )?))
+            }
+            InputFormat::Csv => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .delimiter(delimiter)
+                    .from_reader(input);
+
+                // an empty input has no header to map
+                let header = reader.headers()?;
+                let columns = match header.is_empty() {
+                    true => ColumnMap::default(),
+                    false => ColumnMap::from_header(header).map_err(AppError::InvalidHeader)?,
+                };
+                Ok(TransactionReader::Csv {
+                    reader,
+                    columns,
+                    record: ByteRecord::new(),
+                })
+            }
+            InputFormat::JsonLines => Ok(TransactionReader::JsonLines {
+                reader: BufReader::new(input),
+                line: Vec::new(),
+                number: 0,
+            }),
+            InputFormat::Binary => {
+                let mut reader = BufReader::new(input);
+                read_header(&mut reader)?;
+                Ok(TransactionReader::Binary {
+                    reader,
+                    record: [0; RECORD_LEN],
+                    number: 0,
+                })
+            }
+        }
+    }
+
+    /// The next row, or `None` at the end of the input.
+    pub fn next_row(&mut self) -> Result<Option<InputRow>, AppError> {
+        match self {
+            TransactionReader::Csv {
+                reader,
+                columns,
+                record,
+            } => {
+                // each row is parsed straight from a single record buffer
+                match reader.read_byte_record(record) {
+                    Ok(true) => {}
+                    Ok(false) => return Ok(None),
+                    // an unreadable input cannot be skipped past
+                    Err(err) if err.is_io_error() => return Err(err.into()),
+                    Err(err) => {
+                        let line = err.position().map_or(0, |position| position.line());
+                        return Ok(Some(InputRow::Invalid(line, err.to_string())));
+                    }
+                }
+                let line = record.position().map_or(0, |position| position.line());
+                Ok(Some(match Transaction::from_byte_record(record, columns) {
+                    Ok(transaction) => InputRow::Transaction(line, transaction),
+                    Err(err) => InputRow::Invalid(line, err.to_string()),
+                }))
+            }
+            TransactionReader::JsonLines {
+                reader,
+                line,
+                number,
+            } => loop {
+                line.clear();
+                if reader.read_until(b'\n', line)? == 0 {
+                    return Ok(None);
+                }
+                *number += 1;
+
+                // blank lines carry no transaction
+                let text = match std::str::from_utf8(line) {
+                    Ok(text) if text.trim().is_empty() => continue,
+                    Ok(text) => text,
+                    Err(err) => return Ok(Some(InputRow::Invalid(*number, err.to_string()))),
+                };
+                return Ok(Some(match Transaction::from_json(text) {
+                    Ok(transaction) => InputRow::Transaction(*number, transaction),
+                    Err(err) => InputRow::Invalid(*number, err.to_string()),
+                }));
+            },
+            TransactionReader::Binary {
+                reader,
+                record,
+                number,
+            } => {
+                let read = read_full(reader, record)?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                *number += 1;
+
+                // a partial record can only be the end of a cut off file
+                if read < RECORD_LEN {
+                    let message = format!("Truncated record of {} bytes", read);
+                    return Ok(Some(InputRow::Invalid(*number, message)));
+                }
+                Ok(Some(match decode_record(record) {
+                    Ok(transaction) => InputRow::Transaction(*number, transaction),
+                    Err(message) => InputRow::Invalid(*number, message),
+                }))
+            }
+            #[cfg(feature = "parquet")]
+            TransactionReader::Batches(reader) => reader.next_row(),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn read_all<R: Read>(mut input: R) -> Result<Vec<u8>, AppError> {
+    let mut content = Vec::new();
+    input.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("day.jsonl", InputFormat::JsonLines)]
+    #[case("day.NDJSON.gz", InputFormat::JsonLines)]
+    #[case("day.jsonl.zst", InputFormat::JsonLines)]
+    #[case("day.txbin.zst", InputFormat::Binary)]
+    #[case("day.csv.gz", InputFormat::Csv)]
+    #[case("day.json", InputFormat::Csv)]
+    #[case("jsonl", InputFormat::Csv)]
+    fn test_from_path(#[case] path: &str, #[case] expected: InputFormat) {
+        assert_eq!(InputFormat::from_path(Path::new(path)), expected);
+    }
+
+    #[test]
+    fn test_json_lines_rows() {
+        // Arrange
+        let input = "{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": 2}\n\
+                     \n\
+                     {\"type\": \"deposit\"}\n\
+                     {\"type\": \"withdrawal\", \"client\": 1, \"tx\": 2, \"amount\": \"1.5\"}";
+        let mut reader =
+            TransactionReader::new(input.as_bytes(), InputFormat::JsonLines, b',').unwrap();
+
+        // Act
+        let mut lines = Vec::new();
+        while let Some(row) = reader.next_row().unwrap() {
+            lines.push(match row {
+                InputRow::Transaction(line, _) => (line, true),
+                InputRow::Invalid(line, _) => (line, false),
+            });
+        }
+
+        // Assert
+        assert_eq!(lines, vec![(1, true), (3, false), (4, true)]);
+    }
+
+    #[test]
+    fn test_binary_rows() {
+        // Arrange
+        let mut writer = crate::common::wire::WireWriter::new(Vec::new()).unwrap();
+        for fields in [
+            vec!["deposit", "1", "1", "2.5"],
+            vec!["dispute", "1", "1", ""],
+        ] {
+            let transaction = Transaction::try_from(csv::StringRecord::from(fields)).unwrap();
+            writer.write(&transaction).unwrap();
+        }
+        let mut input = writer.finish().unwrap();
+        input.extend_from_slice(&[0, 1, 1]);
+        let mut reader =
+            TransactionReader::new(input.as_slice(), InputFormat::Binary, b',').unwrap();
+
+        // Act
+        let mut lines = Vec::new();
+        while let Some(row) = reader.next_row().unwrap() {
+            lines.push(match row {
+                InputRow::Transaction(line, _) => (line, true),
+                InputRow::Invalid(line, _) => (line, false),
+            });
+        }
+
+        // Assert
+        assert_eq!(lines, vec![(1, true), (2, true), (3, false)]);
+    }
+}
@@ -1,5 +1,30 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+// Identifier widths are selected with cargo features; the defaults follow the spec.
+#[cfg(not(any(feature = "client-id-u32", feature = "client-id-u64")))]
 pub type ClientId = u16;
+#[cfg(all(feature = "client-id-u32", not(feature = "client-id-u64")))]
+pub type ClientId = u32;
+#[cfg(feature = "client-id-u64")]
+pub type ClientId = u64;
+
+#[cfg(not(any(feature = "tx-id-u64", feature = "tx-id-string")))]
 pub type TransactionId = u32;
+#[cfg(all(feature = "tx-id-u64", not(feature = "tx-id-string")))]
+pub type TransactionId = u64;
+#[cfg(feature = "tx-id-string")]
+pub type TransactionId = OpaqueId;
+
+/// Seconds since the Unix epoch.
+pub type Timestamp = u64;
+
+// parsed so the unit tests work with every id width
+#[cfg(test)]
+pub(crate) fn tx_id(id: u32) -> TransactionId {
+    id.to_string().parse().unwrap()
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TransactionType {
@@ -9,3 +34,126 @@ pub enum TransactionType {
     Resolve,
     Chargeback,
 }
+
+impl TransactionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+        }
+    }
+}
+
+/// Type names are matched regardless of case, e.g. `Deposit` or `DEPOSIT`.
+impl FromStr for TransactionType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let types = [
+            TransactionType::Deposit,
+            TransactionType::Withdrawal,
+            TransactionType::Dispute,
+            TransactionType::Resolve,
+            TransactionType::Chargeback,
+        ];
+        types
+            .into_iter()
+            .find(|tx_type| tx_type.as_str().eq_ignore_ascii_case(s))
+            .ok_or(())
+    }
+}
+
+/**
+ * OpaqueId is an identifier given as a string (e.g. a UUID). It is stored inline,
+ * so it stays `Copy` like the numeric ids.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OpaqueId {
+    len: u8,
+    bytes: [u8; OpaqueId::CAPACITY],
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum OpaqueIdError {
+    #[error("id is empty")]
+    Empty,
+
+    #[error("id is longer than {} bytes", OpaqueId::CAPACITY)]
+    TooLong,
+}
+
+impl OpaqueId {
+    pub const CAPACITY: usize = 39;
+
+    pub fn as_str(&self) -> &str {
+        // only ever built from a valid &str
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap()
+    }
+}
+
+impl FromStr for OpaqueId {
+    type Err = OpaqueIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(OpaqueIdError::Empty);
+        }
+        if s.len() > OpaqueId::CAPACITY {
+            return Err(OpaqueIdError::TooLong);
+        }
+
+        let mut bytes = [0; OpaqueId::CAPACITY];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+
+        Ok(OpaqueId {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+}
+
+impl fmt::Display for OpaqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for OpaqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opaque_id_round_trip() {
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+        let id = OpaqueId::from_str(uuid).unwrap();
+
+        assert_eq!(id.as_str(), uuid);
+        assert_eq!(id.to_string(), uuid);
+        assert_eq!(id, OpaqueId::from_str(uuid).unwrap());
+    }
+
+    #[test]
+    fn test_opaque_id_invalid() {
+        let too_long = "x".repeat(OpaqueId::CAPACITY + 1);
+
+        assert_eq!(OpaqueId::from_str(""), Err(OpaqueIdError::Empty));
+        assert_eq!(OpaqueId::from_str(&too_long), Err(OpaqueIdError::TooLong));
+    }
+
+    #[test]
+    fn test_transaction_type_ignores_case() {
+        assert_eq!("Deposit".parse(), Ok(TransactionType::Deposit));
+        assert_eq!("CHARGEBACK".parse(), Ok(TransactionType::Chargeback));
+        assert_eq!("refund".parse::<TransactionType>(), Err(()));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::common::codec::Codec;
+use crate::common::types::{OpaqueId, TransactionType};
+use crate::models::transaction::Transaction;
+use rust_decimal::Decimal;
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+/// Every binary transaction file starts with this, followed by the format version.
+pub const WIRE_MAGIC: [u8; 6] = *b"TXWIRE";
+pub const WIRE_VERSION: u16 = 1;
+pub const HEADER_LEN: usize = WIRE_MAGIC.len() + 2;
+
+/// The size of one transaction record. The layout, all little endian:
+///
+/// | offset | size | field                                           |
+/// |--------|------|-------------------------------------------------|
+/// | 0      | 1    | type: deposit 0, withdrawal 1, dispute 2, resolve 3, chargeback 4 |
+/// | 1      | 1    | flags: 1 if there is an amount, 2 if there is a timestamp |
+/// | 2      | 1    | scale of the amount, at most 4                  |
+/// | 3      | 1    | reserved, 0                                     |
+/// | 4      | 8    | client id                                       |
+/// | 12     | 8    | transaction id                                  |
+/// | 20     | 8    | timestamp in seconds since the Unix epoch       |
+/// | 28     | 16   | amount as a signed integer at its scale         |
+pub const RECORD_LEN: usize = 44;
+
+const HAS_AMOUNT: u8 = 1;
+const HAS_TIMESTAMP: u8 = 2;
+// amounts are kept to the precision of the engine
+const MAX_SCALE: u32 = 4;
+
+#[derive(Error, Debug)]
+pub enum WireError {
+    #[error("not a binary transaction file")]
+    NotWireFormat,
+
+    #[error("unsupported binary format version {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("transaction {0} cannot be encoded: {1}")]
+    Unencodable(String, &'static str),
+
+    #[error("failed to read or write binary transactions: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Identifiers as the 64-bit integers of a record.
+pub trait WireId: Sized {
+    fn to_wire(&self) -> Option<u64>;
+    fn from_wire(value: u64) -> Option<Self>;
+}
+
+macro_rules! impl_wire_id_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl WireId for $t {
+                fn to_wire(&self) -> Option<u64> {
+                    Some(u64::from(*self))
+                }
+
+                fn from_wire(value: u64) -> Option<Self> {
+                    <$t>::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_wire_id_for_int!(u16, u32, u64);
+
+// only string ids that are plain numbers fit, and only if they read back the same
+impl WireId for OpaqueId {
+    fn to_wire(&self) -> Option<u64> {
+        let value: u64 = self.as_str().parse().ok()?;
+        (value.to_string() == self.as_str()).then_some(value)
+    }
+
+    fn from_wire(value: u64) -> Option<Self> {
+        value.to_string().parse().ok()
+    }
+}
+
+/// Encodes a transaction as one record. Its metadata is not kept.
+pub fn encode_record(transaction: &Transaction) -> Result<[u8; RECORD_LEN], WireError> {
+    let unencodable =
+        |reason| WireError::Unencodable(transaction.get_transaction_id().to_string(), reason);
+
+    let mut record = Vec::with_capacity(RECORD_LEN);
+    transaction.get_transaction_type().encode(&mut record);
+
+    let amount = transaction
+        .get_amount()
+        .map(|amount| amount.round_dp(MAX_SCALE));
+    let mut flags = 0;
+    if amount.is_some() {
+        flags |= HAS_AMOUNT;
+    }
+    if transaction.get_timestamp().is_some() {
+        flags |= HAS_TIMESTAMP;
+    }
+    record.push(flags);
+    record.push(amount.map_or(0, |amount| amount.scale() as u8));
+    record.push(0);
+
+    let client_id = transaction.get_client_id().to_wire();
+    let tx_id = transaction.get_transaction_id().to_wire();
+    client_id
+        .ok_or_else(|| unencodable("the client id is not a 64-bit integer"))?
+        .encode(&mut record);
+    tx_id
+        .ok_or_else(|| unencodable("the transaction id is not a 64-bit integer"))?
+        .encode(&mut record);
+
+    transaction.get_timestamp().unwrap_or(0).encode(&mut record);
+    // any decimal fits, so the amount reads back exactly
+    amount
+        .map_or(0, |amount| amount.mantissa())
+        .encode(&mut record);
+
+    // safe to unwrap because every field has a fixed width
+    Ok(record.try_into().unwrap())
+}
+
+/// Decodes one record, checking it the way a CSV row is checked.
+pub fn decode_record(record: &[u8; RECORD_LEN]) -> Result<Transaction, String> {
+    let mut bytes = &record[..];
+    let tx_type = TransactionType::decode(&mut bytes)
+        .ok_or_else(|| format!("Invalid transaction type {}", record[0]))?;
+    // safe to unwrap because the record is long enough for every field
+    let flags = u8::decode(&mut bytes).unwrap();
+    let scale = u8::decode(&mut bytes).unwrap() as u32;
+    let reserved = u8::decode(&mut bytes).unwrap();
+    let client_id = u64::decode(&mut bytes).unwrap();
+    let tx_id = u64::decode(&mut bytes).unwrap();
+    let timestamp = u64::decode(&mut bytes).unwrap();
+    let mantissa = i128::decode(&mut bytes).unwrap();
+
+    if flags & !(HAS_AMOUNT | HAS_TIMESTAMP) != 0 || reserved != 0 {
+        return Err("Invalid record flags".into());
+    }
+    if scale > MAX_SCALE {
+        return Err(format!("Invalid amount scale {}", scale));
+    }
+
+    let client_id =
+        WireId::from_wire(client_id).ok_or_else(|| format!("Invalid client id {}", client_id))?;
+    let tx_id =
+        WireId::from_wire(tx_id).ok_or_else(|| format!("Invalid transaction id {}", tx_id))?;
+
+    // the same rules as for a CSV row: only deposits and withdrawals have an amount
+    let has_amount = flags & HAS_AMOUNT != 0;
+    let amount = match (&tx_type, has_amount) {
+        (TransactionType::Deposit | TransactionType::Withdrawal, true) => Some(
+            Decimal::try_from_i128_with_scale(mantissa, scale)
+                .map_err(|_| "Invalid amount".to_string())?,
+        ),
+        (TransactionType::Deposit | TransactionType::Withdrawal, false) => {
+            return Err("Invalid amount".into())
+        }
+        (_, true) => return Err("An amount should be empty".into()),
+        (_, false) => None,
+    };
+
+    let transaction = Transaction::new(tx_id, tx_type, client_id, amount);
+    Ok(match flags & HAS_TIMESTAMP != 0 {
+        true => transaction.with_timestamp(timestamp),
+        false => transaction,
+    })
+}
+
+/// Reads and checks the header of a binary transaction file.
+pub fn read_header<R: Read>(reader: &mut R) -> Result<(), WireError> {
+    let mut header = [0; HEADER_LEN];
+    if read_full(reader, &mut header)? < HEADER_LEN || header[..WIRE_MAGIC.len()] != WIRE_MAGIC {
+        return Err(WireError::NotWireFormat);
+    }
+    let version = u16::from_le_bytes([header[WIRE_MAGIC.len()], header[WIRE_MAGIC.len() + 1]]);
+    if version != WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// Fills `buf` as far as the reader goes, returning how much was read. Less than the whole
+/// buffer means the reader ended.
+pub fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/**
+ * WireWriter writes transactions as a binary transaction file. `finish` flushes the writer.
+ */
+pub struct WireWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> WireWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&WIRE_MAGIC)?;
+        writer.write_all(&WIRE_VERSION.to_le_bytes())?;
+        Ok(WireWriter { writer })
+    }
+
+    pub fn write(&mut self, transaction: &Transaction) -> Result<(), WireError> {
+        let record = encode_record(transaction)?;
+        self.writer.write_all(&record)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::{ClientId, TransactionId};
+    use csv::StringRecord;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(vec!["deposit", "1", "1", "10.0"])]
+    #[case(vec!["withdrawal", "65535", "4294967295", "0.0001"])]
+    #[case(vec!["deposit", "2", "3", "-12.34567"])]
+    #[case(vec!["dispute", "1", "1", "", "1700000000"])]
+    #[case(vec!["chargeback", "1", "1", ""])]
+    fn test_round_trip(#[case] fields: Vec<&str>) {
+        // Arrange
+        let transaction = Transaction::try_from(StringRecord::from(fields)).unwrap();
+
+        // Act
+        let record = encode_record(&transaction).unwrap();
+        let decoded = decode_record(&record).unwrap();
+
+        // Assert
+        assert_eq!(decoded, transaction);
+        assert_eq!(
+            decoded.get_amount().map(|amount| amount.to_string()),
+            transaction.get_amount().map(|amount| amount.to_string())
+        );
+    }
+
+    #[rstest]
+    #[case(0, 9, "Invalid transaction type 9")]
+    #[case(1, 4, "Invalid record flags")]
+    #[case(2, 5, "Invalid amount scale 5")]
+    #[case(1, 0, "Invalid amount")]
+    fn test_invalid_record(#[case] offset: usize, #[case] value: u8, #[case] message: &str) {
+        // Arrange
+        let transaction: Transaction = Transaction::new(
+            TransactionId::from_wire(1).unwrap(),
+            TransactionType::Deposit,
+            ClientId::from_wire(1).unwrap(),
+            Some(Decimal::ONE),
+        );
+        let mut record = encode_record(&transaction).unwrap();
+        record[offset] = value;
+
+        // Act
+        let result = decode_record(&record);
+
+        // Assert
+        assert_eq!(result.unwrap_err(), message);
+    }
+
+    #[test]
+    fn test_header() {
+        // Arrange
+        let mut writer = WireWriter::new(Vec::new()).unwrap();
+        let transaction = Transaction::try_from(StringRecord::from(vec!["deposit", "1", "1", "1"]));
+        writer.write(&transaction.unwrap()).unwrap();
+        let file = writer.finish().unwrap();
+
+        // Act
+        let valid = read_header(&mut &file[..]);
+        let csv = read_header(&mut &b"type,client,tx,amount\n"[..]);
+
+        // Assert
+        assert!(valid.is_ok());
+        assert!(matches!(csv, Err(WireError::NotWireFormat)));
+        assert_eq!(file.len(), HEADER_LEN + RECORD_LEN);
+    }
+}
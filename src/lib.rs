@@ -1,26 +1,82 @@
-use crate::managers::output_manager::{CsvOutputManager, OutputError, OutputManager};
-use crate::managers::transaction_manager::TransactionManager;
+use crate::common::compression::Compression;
+use crate::common::input_format::{InputFormat, InputRow, TransactionReader};
+use crate::common::types::Timestamp;
+use crate::common::wire::{WireError, WireWriter};
+use crate::managers::output_manager::CsvOutputManager;
 use crate::models::client_snapshot::ClientSnapshot;
-use crate::models::transaction::{Transaction, TxError};
+use crate::models::ledger_event::LedgerEvent;
+use crate::models::rejection::{InvalidRow, Location, Rejection};
+use crate::models::transaction::TxError;
+use crate::stores::account_store::AccountStore;
+use crate::stores::disk_transaction_store::DEFAULT_CACHE_ENTRIES;
+use crate::stores::sqlite_store::open_sqlite_stores;
+use crate::stores::transaction_store::TransactionStore;
 use csv::Error as CsvError;
-use std::fs::File;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
 use thiserror::Error;
+use tracing::{field, info, info_span, trace};
 
 pub mod models {
     pub mod client;
     pub mod client_snapshot;
+    pub mod journal_entry;
+    pub mod ledger_event;
+    pub mod rejection;
     pub mod transaction;
 }
 
 mod managers {
+    pub mod audit;
+    pub mod journal;
+    pub mod ledger_replay;
+    pub mod metrics;
     pub mod output_manager;
     pub mod transaction_manager;
 }
-mod common {
+mod stores {
+    pub mod account_store;
+    pub mod disk_transaction_store;
+    pub mod sqlite_store;
+    pub mod store_error;
+    pub mod transaction_store;
+}
+pub mod common {
+    pub mod codec;
+    #[cfg(feature = "parquet")]
+    pub mod columnar;
+    pub mod compression;
+    pub mod input_files;
+    pub mod input_format;
     pub mod types;
+    pub mod wire;
+}
+pub mod support {
+    pub mod differential;
+    pub mod generator;
+    pub mod reference;
 }
 
+// the managers and stores are internal, these are the parts of them the binary and library
+// users work with
+pub use crate::managers::audit::AuditFinding;
+pub use crate::managers::journal::{JournalError, TrialBalance};
+pub use crate::managers::ledger_replay::{replay, replay_until_position};
+pub use crate::managers::metrics::Metrics;
+#[cfg(feature = "parquet")]
+pub use crate::managers::output_manager::ParquetOutputManager;
+pub use crate::managers::output_manager::{
+    write_accounts, write_events, write_rejections, write_rows, write_transactions,
+    write_trial_balance, OutputError, OutputManager,
+};
+pub use crate::managers::transaction_manager::{TransactionManager, TxError as ProcessingError};
+pub use crate::stores::account_store::MemoryAccountStore;
+pub use crate::stores::disk_transaction_store::DiskTransactionStore;
+pub use crate::stores::store_error::StoreError;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("failed to read from CSV: {0}")]
@@ -31,28 +87,521 @@ pub enum AppError {
 
     #[error("failed to parse transaction: {0}")]
     TxError(#[from] TxError),
+
+    #[error("failed to access the store: {0}")]
+    StoreError(String),
+
+    #[error("the books do not balance: {0}")]
+    JournalError(String),
+
+    #[error("invalid header: {0}")]
+    InvalidHeader(TxError),
+
+    #[error("invalid row on {}: {}", .0.get_location(), .0.get_message())]
+    InvalidRow(InvalidRow),
+
+    #[error("transaction rejected on {}: {}", .0.get_location(), .0.get_error())]
+    Rejected(Box<Rejection>),
+
+    #[error("stopped after {0} errors, the last one being {1}")]
+    TooManyErrors(u64, Box<AppError>),
+
+    #[error("invalid binary input: {0}")]
+    WireError(#[from] WireError),
+
+    #[cfg(feature = "parquet")]
+    #[error("failed to read Parquet or Arrow input: {0}")]
+    ColumnarReadError(String),
+}
+
+impl From<StoreError> for AppError {
+    fn from(err: StoreError) -> Self {
+        AppError::StoreError(err.to_string())
+    }
+}
+
+/// Where the accounts and the transaction history are kept.
+#[derive(Debug, Clone, Default)]
+pub enum StoreKind {
+    #[default]
+    Memory,
+    /// Keeps the transaction history in an embedded database file with a bounded in-memory
+    /// cache. A temporary file is used when no path is given.
+    Disk(Option<PathBuf>),
+    /// Keeps both accounts and transaction history in a SQLite file.
+    Sqlite(PathBuf),
+}
+
+/// What to do with rows that cannot be parsed or that the ledger rejects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stops the run at the first such row.
+    Abort,
+    /// Skips such rows and lists them in the `RunReport`.
+    Skip,
+    /// Skips such rows like `Skip`, but stops the run at the Nth.
+    AbortAfter(u64),
+}
+
+impl ErrorPolicy {
+    // whether the run may go on once `errors` rows, this one included, have failed
+    fn tolerates(&self, errors: u64) -> bool {
+        match self {
+            ErrorPolicy::Abort => false,
+            ErrorPolicy::Skip => true,
+            ErrorPolicy::AbortAfter(limit) => errors < *limit,
+        }
+    }
+
+    fn abort(&self, errors: u64, err: AppError) -> AppError {
+        match self {
+            ErrorPolicy::AbortAfter(_) => AppError::TooManyErrors(errors, Box::new(err)),
+            _ => err,
+        }
+    }
+}
+
+/// Parses `abort`, `skip` or a number of errors to abort after.
+impl FromStr for ErrorPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(ErrorPolicy::Abort),
+            "skip" => Ok(ErrorPolicy::Skip),
+            _ => match s.parse::<u64>() {
+                Ok(limit) if limit > 0 => Ok(ErrorPolicy::AbortAfter(limit)),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Disputes filed more than this many seconds after the disputed transaction are rejected.
+    pub dispute_window: Option<Timestamp>,
+    /// Transaction ids are unique across all clients rather than per client.
+    pub global_tx_ids: bool,
+    pub store: StoreKind,
+    /// Keeps every accepted operation as a `LedgerEvent` in the `RunReport`.
+    pub record_events: bool,
+    /// Posts every accepted operation to a double-entry journal and reports its trial balance.
+    pub bookkeeping: bool,
+    /// Checks the accounts against the transaction history once every row is processed.
+    pub verify: bool,
+    /// The format of every input. Unset, a file's format is told by its extension, see
+    /// `InputFormat::from_path`, and a reader is read as CSV.
+    pub input_format: Option<InputFormat>,
+    /// The field delimiter of CSV input, a comma by default.
+    pub delimiter: u8,
+    /// Applies to rows that cannot be read as a transaction. Stops the run by default.
+    pub invalid_rows: ErrorPolicy,
+    /// Applies to transactions the ledger refuses. Skips them by default.
+    pub rejections: ErrorPolicy,
+    /// Keeps every skipped transaction as a `Rejection` in the `RunReport`. Only the number of
+    /// rejections per kind is kept otherwise, so memory does not grow with them.
+    pub record_rejections: bool,
+    /// Counts and times the rows and transactions of the run, see `managers::metrics`.
+    pub metrics: bool,
+}
+
+/// The outcome of processing a file.
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    /// Data rows read, invalid ones included.
+    pub rows: u64,
+    pub clients: Vec<ClientSnapshot>,
+    /// Empty unless `RunOptions::record_events` is set.
+    pub events: Vec<LedgerEvent>,
+    /// Set if `RunOptions::bookkeeping` is set.
+    pub trial_balance: Option<TrialBalance>,
+    /// Client accounts that disagree with the journal.
+    pub reconciliation: Vec<JournalError>,
+    /// Empty unless `RunOptions::verify` is set and the accounts are inconsistent.
+    pub audit: Vec<AuditFinding>,
+    /// The number of transactions skipped under `RunOptions::rejections` for each `TxError`
+    /// kind.
+    pub rejection_counts: BTreeMap<&'static str, u64>,
+    /// The transactions skipped under `RunOptions::rejections`. Empty unless
+    /// `RunOptions::record_rejections` is set.
+    pub rejections: Vec<Rejection>,
+    /// The rows skipped under `RunOptions::invalid_rows`.
+    pub invalid_rows: Vec<InvalidRow>,
+    /// Set if `RunOptions::metrics` is set.
+    pub metrics: Option<Metrics>,
+}
+
+impl RunReport {
+    /// The number of rejected transactions, recorded or not.
+    pub fn get_rejected(&self) -> u64 {
+        self.rejection_counts.values().sum()
+    }
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            dispute_window: None,
+            global_tx_ids: true,
+            store: StoreKind::Memory,
+            record_events: false,
+            bookkeeping: false,
+            verify: false,
+            input_format: None,
+            delimiter: b',',
+            invalid_rows: ErrorPolicy::Abort,
+            rejections: ErrorPolicy::Skip,
+            record_rejections: false,
+            metrics: false,
+        }
+    }
 }
 
 pub fn run_transactions_from_file<P: AsRef<Path>>(
     path: P,
 ) -> Result<Vec<ClientSnapshot>, AppError> {
-    let file = File::open(path)?;
-    let mut reader = csv::Reader::from_reader(file);
-    let mut transaction_manager = TransactionManager::new();
+    let report = run_transactions_from_file_with_options(path, &RunOptions::default())?;
+    Ok(report.clients)
+}
 
-    // read the csv file; each row is streamed into the transaction manager
-    for result in reader.records() {
-        let transaction = Transaction::try_from(result?)?;
+pub fn run_transactions_from_file_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &RunOptions,
+) -> Result<RunReport, AppError> {
+    let path = path.as_ref();
+    // compressed files are decompressed while they are read
+    let format = input_format(path, options);
+    let input = Input {
+        file: None,
+        reader: TransactionReader::open(path, format, options.delimiter)?,
+    };
+    run_inputs(std::iter::once(Ok(input)), options)
+}
+
+/// Processes input from any reader, the way `run_transactions_from_file_with_options` does. It
+/// is read as CSV unless `RunOptions::input_format` says otherwise.
+pub fn run_transactions_from_reader<R: Read>(
+    input: R,
+    options: &RunOptions,
+) -> Result<RunReport, AppError> {
+    let format = options.input_format.unwrap_or_default();
+    let input = Input {
+        file: None,
+        reader: TransactionReader::new(input, format, options.delimiter)?,
+    };
+    run_inputs(std::iter::once(Ok(input)), options)
+}
 
-        // just ignore any errors for now
-        let _ = transaction_manager.add_transaction(transaction);
+/// Processes several files, in the order given, through the same ledger. Rejections and
+/// invalid rows carry the file they came from.
+pub fn run_transactions_from_files<P: AsRef<Path>>(
+    paths: &[P],
+    options: &RunOptions,
+) -> Result<RunReport, AppError> {
+    // each file is only opened once the previous one has been read
+    let inputs = paths.iter().map(|path| {
+        let path = path.as_ref();
+        let format = input_format(path, options);
+        Ok(Input {
+            file: Some(path.to_path_buf()),
+            reader: TransactionReader::open(path, format, options.delimiter)?,
+        })
+    });
+    run_inputs(inputs, options)
+}
+
+/// The outcome of converting files to the binary format.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertReport {
+    /// Transactions written.
+    pub rows: u64,
+    /// The rows skipped under `RunOptions::invalid_rows`, including those that do not fit a
+    /// binary record.
+    pub invalid_rows: Vec<InvalidRow>,
+}
+
+/// Converts the transactions of several files, in the order given, to one file in the binary
+/// format of `common::wire`. Only the input and invalid row options apply.
+pub fn convert_files_to_binary<P: AsRef<Path>, W: Write>(
+    paths: &[P],
+    options: &RunOptions,
+    writer: W,
+) -> Result<ConvertReport, AppError> {
+    let mut writer = WireWriter::new(writer)?;
+    let mut report = ConvertReport::default();
+
+    for path in paths.iter() {
+        let path = path.as_ref();
+        let format = input_format(path, options);
+        let mut reader = TransactionReader::open(path, format, options.delimiter)?;
+
+        while let Some(row) = reader.next_row()? {
+            let (line, message) = match row {
+                InputRow::Transaction(line, transaction) => match writer.write(&transaction) {
+                    Ok(()) => {
+                        report.rows += 1;
+                        continue;
+                    }
+                    Err(WireError::Io(err)) => return Err(err.into()),
+                    Err(err) => (line, err.to_string()),
+                },
+                InputRow::Invalid(line, message) => (line, message),
+            };
+            let location = Location::new(Some(path.to_path_buf()), line);
+            let invalid_row = InvalidRow::new(location, message);
+            skip_invalid_row(&mut report.invalid_rows, invalid_row, options)?;
+        }
     }
 
-    let results = transaction_manager.get_all_values();
-    Ok(results)
+    writer.finish()?;
+    Ok(report)
+}
+
+// one input of a run; the file is only known when several are read
+struct Input<R: Read> {
+    file: Option<PathBuf>,
+    reader: TransactionReader<R>,
+}
+
+fn input_format(path: &Path, options: &RunOptions) -> InputFormat {
+    options
+        .input_format
+        .unwrap_or_else(|| InputFormat::from_path(path))
+}
+
+fn run_inputs<R, I>(inputs: I, options: &RunOptions) -> Result<RunReport, AppError>
+where
+    R: Read,
+    I: Iterator<Item = Result<Input<R>, AppError>>,
+{
+    match &options.store {
+        StoreKind::Memory => {
+            let manager = TransactionManager::new();
+            process_transactions(inputs, manager, options)
+        }
+        StoreKind::Disk(path) => {
+            let tx_history = match path {
+                Some(path) => {
+                    // the accounts start empty, so an earlier run's history would reject
+                    // its transactions as duplicates
+                    let mut tx_history = DiskTransactionStore::open(path, DEFAULT_CACHE_ENTRIES)?;
+                    if !tx_history.is_empty()? {
+                        return Err(StoreError::HistoryNotEmpty(path.clone()).into());
+                    }
+                    tx_history
+                }
+                None => DiskTransactionStore::temporary(DEFAULT_CACHE_ENTRIES)?,
+            };
+            let manager = TransactionManager::with_stores(MemoryAccountStore::new(), tx_history);
+            process_transactions(inputs, manager, options)
+        }
+        StoreKind::Sqlite(path) => {
+            let (client_db, tx_history) = open_sqlite_stores(path)?;
+            let manager = TransactionManager::with_stores(client_db, tx_history);
+            process_transactions(inputs, manager, options)
+        }
+    }
+}
+
+fn process_transactions<R, I, A, T>(
+    inputs: I,
+    transaction_manager: TransactionManager<A, T>,
+    options: &RunOptions,
+) -> Result<RunReport, AppError>
+where
+    R: Read,
+    I: Iterator<Item = Result<Input<R>, AppError>>,
+    A: AccountStore,
+    T: TransactionStore,
+{
+    let mut transaction_manager = transaction_manager.with_global_tx_ids(options.global_tx_ids);
+    if let Some(window) = options.dispute_window {
+        transaction_manager = transaction_manager.with_dispute_window(window);
+    }
+    if options.record_events {
+        transaction_manager = transaction_manager.with_event_log();
+    }
+    if options.bookkeeping {
+        transaction_manager = transaction_manager.with_journal();
+    }
+    if options.metrics {
+        transaction_manager = transaction_manager.with_metrics();
+    }
+
+    let mut rows = 0;
+    let mut rejected = 0;
+    let mut rejection_counts = BTreeMap::new();
+    let mut rejections = Vec::new();
+    // the transaction is only kept around when a rejection may have to be reported
+    let keep_rejected = options.record_rejections || options.rejections != ErrorPolicy::Skip;
+    let mut invalid_rows = Vec::new();
+
+    for input in inputs {
+        let Input { file, mut reader } = input?;
+        let span = info_span!("input", file = field::Empty);
+        if let Some(file) = &file {
+            span.record("file", field::display(file.display()));
+        }
+        let _entered = span.enter();
+
+        // each row is streamed into the transaction manager
+        loop {
+            let started = options.metrics.then(Instant::now);
+            let row = match parse_row(&mut reader)? {
+                Some(row) => row,
+                None => break,
+            };
+            if let (Some(started), Some(metrics)) = (started, transaction_manager.metrics_mut()) {
+                let valid = matches!(row, InputRow::Transaction(_, _));
+                metrics.observe_row(valid, started.elapsed());
+            }
+            rows += 1;
+            let (line, transaction) = match row {
+                InputRow::Transaction(line, transaction) => (line, transaction),
+                InputRow::Invalid(line, message) => {
+                    let location = Location::new(file.clone(), line);
+                    let invalid_row = InvalidRow::new(location, message);
+                    skip_invalid_row(&mut invalid_rows, invalid_row, options)?;
+                    continue;
+                }
+            };
+            let rejected_transaction = keep_rejected.then(|| transaction.clone());
+
+            // a broken store or journal always stops the run
+            match transaction_manager.add_transaction(transaction) {
+                Err(ProcessingError::StoreFailure(err)) => return Err(AppError::StoreError(err)),
+                Err(ProcessingError::JournalFailure(err)) => {
+                    return Err(AppError::JournalError(err))
+                }
+                Err(err) => {
+                    rejected += 1;
+                    *rejection_counts.entry(err.kind()).or_insert(0) += 1;
+                    let Some(transaction) = rejected_transaction else {
+                        continue;
+                    };
+                    let location = Location::new(file.clone(), line);
+                    let rejection = Rejection::new(location, transaction, err);
+                    if !options.rejections.tolerates(rejected) {
+                        let err = AppError::Rejected(Box::new(rejection));
+                        return Err(options.rejections.abort(rejected, err));
+                    }
+                    if options.record_rejections {
+                        rejections.push(rejection);
+                    }
+                }
+                Ok(()) => {}
+            }
+        }
+    }
+
+    let audit = match options.verify {
+        true => match transaction_manager.audit() {
+            Ok(findings) => findings,
+            Err(err) => return Err(AppError::StoreError(err.to_string())),
+        },
+        false => Vec::new(),
+    };
+
+    // open disputes are counted in the store, which may carry disputes from earlier runs
+    let open_disputes = match options.metrics {
+        true => match transaction_manager.count_open_disputes() {
+            Ok(open_disputes) => open_disputes,
+            Err(err) => return Err(AppError::StoreError(err.to_string())),
+        },
+        false => 0,
+    };
+
+    // a run that stopped early has returned above without committing
+    if let Err(err) = transaction_manager.commit() {
+        return Err(AppError::StoreError(err.to_string()));
+    }
+
+    let events = transaction_manager.take_events();
+    let journal = transaction_manager.take_journal();
+    let mut metrics = transaction_manager.take_metrics();
+    let clients = match transaction_manager.get_all_values() {
+        Ok(clients) => clients,
+        Err(err) => return Err(AppError::StoreError(err.to_string())),
+    };
+    if let Some(metrics) = &mut metrics {
+        metrics.observe_open_disputes(open_disputes);
+        metrics.observe_accounts(&clients);
+    }
+
+    let (trial_balance, reconciliation) = match journal {
+        Some(journal) => (Some(journal.trial_balance()), journal.reconcile(&clients)),
+        None => (None, Vec::new()),
+    };
+
+    Ok(RunReport {
+        rows,
+        clients,
+        events,
+        trial_balance,
+        reconciliation,
+        audit,
+        rejection_counts,
+        rejections,
+        invalid_rows,
+        metrics,
+    })
+}
+
+// reads the next row in a span that carries its line and what it holds
+fn parse_row<R: Read>(reader: &mut TransactionReader<R>) -> Result<Option<InputRow>, AppError> {
+    let span = info_span!(
+        "parse",
+        line = field::Empty,
+        client = field::Empty,
+        tx = field::Empty,
+        r#type = field::Empty,
+        outcome = field::Empty,
+    );
+    let _entered = span.enter();
+
+    let row = reader.next_row()?;
+    match &row {
+        Some(InputRow::Transaction(line, transaction)) => {
+            span.record("line", line);
+            span.record("client", field::display(transaction.get_client_id()));
+            span.record("tx", field::display(transaction.get_transaction_id()));
+            span.record("type", transaction.get_transaction_type().as_str());
+            span.record("outcome", "parsed");
+            trace!("row parsed");
+        }
+        Some(InputRow::Invalid(line, message)) => {
+            span.record("line", line);
+            span.record("outcome", "invalid");
+            info!(error = %message, "invalid row");
+        }
+        None => {}
+    }
+    Ok(row)
+}
+
+fn skip_invalid_row(
+    invalid_rows: &mut Vec<InvalidRow>,
+    invalid_row: InvalidRow,
+    options: &RunOptions,
+) -> Result<(), AppError> {
+    let errors = invalid_rows.len() as u64 + 1;
+    if !options.invalid_rows.tolerates(errors) {
+        let err = AppError::InvalidRow(invalid_row);
+        return Err(options.invalid_rows.abort(errors, err));
+    }
+    invalid_rows.push(invalid_row);
+    Ok(())
 }
 
 pub fn write_output(clients: &[ClientSnapshot]) -> Result<(), OutputError> {
-    let output_manager = CsvOutputManager::new();
+    write_compressed_output(clients, Compression::None)
+}
+
+pub fn write_compressed_output(
+    clients: &[ClientSnapshot],
+    compression: Compression,
+) -> Result<(), OutputError> {
+    let output_manager = CsvOutputManager::new().with_compression(compression);
     output_manager.write_output(clients)
 }
@@ -1,25 +1,582 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use std::error::Error;
-use toy_payments_engine::{run_transactions_from_file, write_output};
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal};
+use std::path::PathBuf;
+use toy_payments_engine::common::compression::Compression;
+use toy_payments_engine::common::input_files::{expand_inputs, read_manifest};
+use toy_payments_engine::common::input_format::InputFormat;
+use toy_payments_engine::common::types::Timestamp;
+use toy_payments_engine::models::client_snapshot::ClientSnapshot;
+use toy_payments_engine::support::generator::{
+    GeneratedRow, GeneratorConfig, TransactionGenerator,
+};
+use toy_payments_engine::support::reference::ReferenceLedger;
+use toy_payments_engine::{
+    convert_files_to_binary, replay_until_position, run_transactions_from_files, write_accounts,
+    write_compressed_output, write_events, write_rejections, write_rows, write_trial_balance,
+    ErrorPolicy, RunOptions, StoreKind,
+};
+#[cfg(feature = "parquet")]
+use toy_payments_engine::{OutputManager, ParquetOutputManager};
+use tracing::level_filters::LevelFilter;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let matches = Command::new("CSV Reader")
+    let command = Command::new("CSV Reader")
         .version("1.0")
         .author("Your Name. <your_email@example.com>")
         .about("Toy Engine")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .args(logging_args())
+        .args(input_args())
+        .args(processing_args())
+        .args(store_args())
+        .args(error_policy_args())
+        .arg(
+            Arg::new("events")
+                .long("events")
+                .help("Write every accepted operation as a ledger event to this CSV file")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("as-of-transaction")
+                .long("as-of-transaction")
+                .help(
+                    "Output the accounts as they were after this many transactions, counting \
+                     rejected ones but not invalid rows, across all input files",
+                )
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("compress-output")
+                .long("compress-output")
+                .help("Compress the accounts written to stdout")
+                .value_parser(|s: &str| {
+                    s.parse::<Compression>()
+                        .map_err(|_| "expected none, gzip or zstd")
+                })
+                .default_value("none"),
+        )
+        .arg(
+            Arg::new("rejects")
+                .long("rejects")
+                .help("Write the skipped transactions the ledger rejected to this CSV file")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("metrics")
+                .long("metrics")
+                .help("Write counters and latency histograms in the Prometheus text format to this file")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
         .arg(
-            Arg::new("filename")
-                .help("The CSV file to read")
-                .required(true)
-                .index(1),
+            Arg::new("verify")
+                .long("verify")
+                .help("Check the accounts against the transaction history and report discrepancies")
+                .action(ArgAction::SetTrue),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("trial-balance")
+                .about("Print the double-entry trial balance instead of the accounts")
+                .args(input_args())
+                .args(processing_args())
+                .args(store_args())
+                .args(error_policy_args()),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Report every invalid row and the transactions that would be rejected")
+                .args(input_args())
+                .args(processing_args())
+                .arg(scratch_store_arg()),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Convert transactions to the compact binary format")
+                .args(input_args())
+                .args(input_format_args())
+                .arg(invalid_rows_arg())
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .help("The binary file to write, by convention ending in .txbin")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("generate")
+                .about("Write a reproducible stream of transactions for a seed")
+                .args(generate_args()),
+        );
+    #[cfg(feature = "parquet")]
+    let command = command.arg(
+        Arg::new("output-format")
+            .long("output-format")
+            .help("Write the accounts to stdout as csv or parquet")
+            .value_parser(["csv", "parquet"])
+            .default_value("csv")
+            .conflicts_with("compress-output"),
+    );
+    let matches = command.get_matches();
+    init_logging(&matches);
+
+    match matches.subcommand() {
+        Some(("trial-balance", sub_matches)) => trial_balance(sub_matches),
+        Some(("validate", sub_matches)) => validate(sub_matches),
+        Some(("convert", sub_matches)) => convert(sub_matches),
+        Some(("generate", sub_matches)) => generate(sub_matches),
+        _ => run(&matches),
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let files = input_files(matches)?;
+
+    let options = RunOptions {
+        record_events: matches.contains_id("events") || matches.contains_id("as-of-transaction"),
+        verify: matches.get_flag("verify"),
+        invalid_rows: error_policy(matches, "invalid-rows"),
+        rejections: error_policy(matches, "rejections"),
+        record_rejections: matches.contains_id("rejects"),
+        metrics: matches.contains_id("metrics"),
+        store: store_kind(matches)?,
+        ..run_options(matches)?
+    };
+
+    let report = run_transactions_from_files(&files, &options)?;
+    if let (Some(path), Some(metrics)) = (matches.get_one::<PathBuf>("metrics"), &report.metrics) {
+        metrics.write_prometheus(BufWriter::new(File::create(path)?))?;
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("events") {
+        write_events(File::create(path)?, &report.events)?;
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("rejects") {
+        write_rejections(File::create(path)?, &report.rejections)?;
+    }
+    for invalid_row in report.invalid_rows.iter() {
+        eprintln!(
+            "skipped {}: {}",
+            invalid_row.get_location(),
+            invalid_row.get_message()
+        );
+    }
+
+    match matches.get_one::<u64>("as-of-transaction") {
+        Some(position) => {
+            let clients = replay_until_position(&report.events, *position)?;
+            write_accounts_output(matches, &clients)?
+        }
+        None => write_accounts_output(matches, &report.clients)?,
+    }
+
+    for finding in report.audit.iter() {
+        eprintln!("{}", finding);
+    }
+    if !report.audit.is_empty() {
+        return Err(format!("the audit found {} discrepancies", report.audit.len()).into());
+    }
+
+    Ok(())
+}
+
+fn write_accounts_output(
+    matches: &ArgMatches,
+    clients: &[ClientSnapshot],
+) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "parquet")]
+    if matches
+        .get_one::<String>("output-format")
+        .map(String::as_str)
+        == Some("parquet")
+    {
+        return Ok(ParquetOutputManager::new().write_output(clients)?);
+    }
+
+    // safe to unwrap because the argument has a default value
+    let compression = *matches.get_one::<Compression>("compress-output").unwrap();
+    Ok(write_compressed_output(clients, compression)?)
+}
+
+fn trial_balance(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let files = input_files(matches)?;
+
+    let options = RunOptions {
+        bookkeeping: true,
+        invalid_rows: error_policy(matches, "invalid-rows"),
+        rejections: error_policy(matches, "rejections"),
+        store: store_kind(matches)?,
+        ..run_options(matches)?
+    };
+
+    let report = run_transactions_from_files(&files, &options)?;
+    let trial_balance = report.trial_balance.unwrap_or_default();
+    write_trial_balance(std::io::stdout(), &trial_balance)?;
+
+    for mismatch in report.reconciliation.iter() {
+        eprintln!("{}", mismatch);
+    }
+    if !trial_balance.is_balanced()? || !report.reconciliation.is_empty() {
+        return Err("the books do not balance".into());
+    }
+
+    Ok(())
+}
+
+fn validate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let files = input_files(matches)?;
+
+    let options = RunOptions {
+        invalid_rows: ErrorPolicy::Skip,
+        rejections: ErrorPolicy::Skip,
+        // a dry run: the disk store is a temporary file and nothing is persisted
+        store: match matches.get_one::<String>("store").map(String::as_str) {
+            Some("disk") => StoreKind::Disk(None),
+            _ => StoreKind::Memory,
+        },
+        ..run_options(matches)?
+    };
+
+    let report = run_transactions_from_files(&files, &options)?;
+    for invalid_row in report.invalid_rows.iter() {
+        println!(
+            "{}: {}",
+            invalid_row.get_location(),
+            invalid_row.get_message()
+        );
+    }
+
+    let invalid = report.invalid_rows.len() as u64;
+    let rejected = report.get_rejected();
+    println!(
+        "{} rows: {} invalid, {} accepted, {} rejected",
+        report.rows,
+        invalid,
+        report.rows - invalid - rejected,
+        rejected
+    );
+    for (kind, count) in &report.rejection_counts {
+        println!("  {}: {}", kind, count);
+    }
+
+    if invalid > 0 {
+        return Err(format!("{} rows are invalid", invalid).into());
+    }
+
+    Ok(())
+}
+
+fn convert(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let files = input_files(matches)?;
+    let options = RunOptions {
+        input_format: matches.get_one::<InputFormat>("input-format").copied(),
+        // safe to unwrap because the argument has a default value
+        delimiter: *matches.get_one::<u8>("delimiter").unwrap(),
+        invalid_rows: error_policy(matches, "invalid-rows"),
+        ..RunOptions::default()
+    };
 
     // safe to unwrap because the argument is required
-    let filename = matches.get_one::<String>("filename").unwrap();
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+    let writer = BufWriter::new(File::create(output)?);
+    let report = convert_files_to_binary(&files, &options, writer)?;
+    for invalid_row in report.invalid_rows.iter() {
+        eprintln!(
+            "skipped {}: {}",
+            invalid_row.get_location(),
+            invalid_row.get_message()
+        );
+    }
+    eprintln!(
+        "{} transactions written to {}",
+        report.rows,
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn generate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let defaults = GeneratorConfig::default();
+    let config = GeneratorConfig {
+        seed: *matches.get_one("seed").unwrap_or(&defaults.seed),
+        clients: *matches.get_one("clients").unwrap_or(&defaults.clients),
+        rows: *matches.get_one("rows").unwrap_or(&defaults.rows),
+        dispute_rate: *matches
+            .get_one("dispute-rate")
+            .unwrap_or(&defaults.dispute_rate),
+        chargeback_rate: *matches
+            .get_one("chargeback-rate")
+            .unwrap_or(&defaults.chargeback_rate),
+        rejection_rate: *matches
+            .get_one("rejection-rate")
+            .unwrap_or(&defaults.rejection_rate),
+        duplicate_rate: *matches
+            .get_one("duplicate-rate")
+            .unwrap_or(&defaults.duplicate_rate),
+        malformed_rate: *matches
+            .get_one("malformed-rate")
+            .unwrap_or(&defaults.malformed_rate),
+    };
+    let generator = TransactionGenerator::new(config)?;
+
+    // the expected accounts come from the reference model, not from the engine under test
+    let mut reference = ReferenceLedger::new();
+    let rows = generator.rows().inspect(|row| {
+        if let GeneratedRow::Transaction(tx) = row {
+            reference.apply(tx);
+        }
+    });
+    match matches.get_one::<PathBuf>("output") {
+        Some(path) => write_rows(File::create(path)?, rows)?,
+        None => write_rows(std::io::stdout(), rows)?,
+    }
 
-    let clients = run_transactions_from_file(filename)?;
-    write_output(&clients)?;
+    if let Some(path) = matches.get_one::<PathBuf>("expected") {
+        write_accounts(File::create(path)?, &reference.get_accounts())?;
+    }
 
     Ok(())
 }
+
+fn generate_args() -> Vec<Arg> {
+    let rate = |name: &'static str, help: &'static str| {
+        Arg::new(name)
+            .long(name)
+            .help(help)
+            .value_parser(clap::value_parser!(f64))
+    };
+
+    vec![
+        Arg::new("seed")
+            .long("seed")
+            .help("The same seed always produces the same transactions")
+            .value_parser(clap::value_parser!(u64)),
+        Arg::new("clients")
+            .long("clients")
+            .help("Number of clients")
+            .value_parser(clap::value_parser!(u64)),
+        Arg::new("rows")
+            .long("rows")
+            .help("Number of transactions")
+            .value_parser(clap::value_parser!(u64)),
+        rate(
+            "dispute-rate",
+            "Share of rows that dispute an earlier transaction",
+        ),
+        rate(
+            "chargeback-rate",
+            "Share of closed disputes that end in a chargeback",
+        ),
+        rate(
+            "rejection-rate",
+            "Share of well-formed rows the engine has to reject",
+        ),
+        rate(
+            "duplicate-rate",
+            "Share of rows that repeat an earlier transaction",
+        ),
+        rate(
+            "malformed-rate",
+            "Share of rows that cannot be parsed, on top of the transactions",
+        ),
+        Arg::new("output")
+            .long("output")
+            .help("Write the transactions to this file instead of stdout")
+            .value_parser(clap::value_parser!(PathBuf)),
+        Arg::new("expected")
+            .long("expected")
+            .help("Write the accounts the transactions should produce to this file")
+            .value_parser(clap::value_parser!(PathBuf)),
+    ]
+}
+
+// log settings apply to every subcommand
+fn logging_args() -> Vec<Arg> {
+    vec![
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .help(
+                "Log rejected and invalid rows to stderr; repeat to log every transaction and row",
+            )
+            .action(ArgAction::Count)
+            .global(true),
+        Arg::new("log-format")
+            .long("log-format")
+            .help("Write the log as text or as one JSON object per line")
+            .value_parser(["text", "json"])
+            .default_value("text")
+            .global(true),
+    ]
+}
+
+// the log goes to stderr so that it never mixes with the accounts on stdout
+fn init_logging(matches: &ArgMatches) {
+    let level = match matches.get_count("verbose") {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_target(false);
+
+    // safe to unwrap because the argument has a default value
+    match matches.get_one::<String>("log-format").unwrap().as_str() {
+        "json" => subscriber.json().init(),
+        _ => subscriber.with_ansi(io::stderr().is_terminal()).init(),
+    }
+}
+
+fn input_args() -> Vec<Arg> {
+    vec![
+        Arg::new("filename")
+            .help("The CSV files to read; a directory or a glob reads its files in lexical order")
+            .required_unless_present("manifest")
+            .num_args(1..)
+            .index(1),
+        Arg::new("manifest")
+            .long("manifest")
+            .help("Read the files listed in this file, one per line, in that order")
+            .value_parser(clap::value_parser!(PathBuf))
+            .conflicts_with("filename"),
+    ]
+}
+
+fn input_files(matches: &ArgMatches) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    match matches.get_one::<PathBuf>("manifest") {
+        Some(manifest) => Ok(read_manifest(manifest)?),
+        None => {
+            // safe to unwrap because the argument is required without a manifest
+            let inputs: Vec<&String> = matches.get_many::<String>("filename").unwrap().collect();
+            Ok(expand_inputs(&inputs)?)
+        }
+    }
+}
+
+fn processing_args() -> Vec<Arg> {
+    vec![
+        Arg::new("dispute-window-days")
+            .long("dispute-window-days")
+            .help("Reject disputes filed more than this many days after the transaction")
+            .value_parser(parse_dispute_window),
+        Arg::new("per-client-tx-ids")
+            .long("per-client-tx-ids")
+            .help("Only require transaction ids to be unique per client")
+            .action(ArgAction::SetTrue),
+    ]
+    .into_iter()
+    .chain(input_format_args())
+    .collect()
+}
+
+fn store_args() -> Vec<Arg> {
+    vec![
+        Arg::new("store")
+            .long("store")
+            .help("Where to keep the accounts and the transaction history")
+            .value_parser(["memory", "disk", "sqlite"])
+            .default_value("memory"),
+        Arg::new("store-path")
+            .long("store-path")
+            .help("Database file for the disk or sqlite store")
+            .value_parser(clap::value_parser!(PathBuf)),
+    ]
+}
+
+// validation never writes to a database that outlives it
+fn scratch_store_arg() -> Arg {
+    Arg::new("store")
+        .long("store")
+        .help("Keep the transaction history in memory or in a temporary file on disk")
+        .value_parser(["memory", "disk"])
+        .default_value("memory")
+}
+
+fn input_format_args() -> Vec<Arg> {
+    vec![
+        Arg::new("input-format")
+            .long("input-format")
+            .help("Read the input as csv, jsonl, binary, parquet or arrow instead of telling by the extension")
+            .value_parser(|s: &str| s.parse::<InputFormat>().map_err(|_| "unknown input format")),
+        Arg::new("delimiter")
+            .long("delimiter")
+            .help("The field delimiter of CSV input, a single character or 'tab'")
+            .value_parser(|s: &str| match s {
+                "tab" | "\\t" => Ok(b'\t'),
+                _ if s.len() == 1 => Ok(s.as_bytes()[0]),
+                _ => Err("expected a single character or 'tab'"),
+            })
+            .default_value(","),
+    ]
+}
+
+fn error_policy_args() -> Vec<Arg> {
+    vec![
+        invalid_rows_arg(),
+        Arg::new("rejections")
+            .long("rejections")
+            .help("What to do with rejected transactions: abort, skip or stop at the Nth")
+            .value_parser(parse_error_policy)
+            .default_value("skip"),
+    ]
+}
+
+fn invalid_rows_arg() -> Arg {
+    Arg::new("invalid-rows")
+        .long("invalid-rows")
+        .help("What to do with rows that cannot be parsed: abort, skip or stop at the Nth")
+        .value_parser(parse_error_policy)
+        .default_value("abort")
+}
+
+// the window is given in days and kept in seconds
+fn parse_dispute_window(s: &str) -> Result<Timestamp, String> {
+    let days = s.parse::<u64>().map_err(|err| err.to_string())?;
+    days.checked_mul(SECONDS_PER_DAY)
+        .ok_or_else(|| format!("more than {} days", Timestamp::MAX / SECONDS_PER_DAY))
+}
+
+fn parse_error_policy(s: &str) -> Result<ErrorPolicy, &'static str> {
+    s.parse::<ErrorPolicy>()
+        .map_err(|_| "expected abort, skip or a number of errors to stop at")
+}
+
+fn error_policy(matches: &ArgMatches, id: &str) -> ErrorPolicy {
+    // safe to unwrap because the argument has a default value
+    *matches.get_one::<ErrorPolicy>(id).unwrap()
+}
+
+fn run_options(matches: &ArgMatches) -> Result<RunOptions, Box<dyn Error>> {
+    Ok(RunOptions {
+        dispute_window: matches.get_one::<Timestamp>("dispute-window-days").copied(),
+        global_tx_ids: !matches.get_flag("per-client-tx-ids"),
+        input_format: matches.get_one::<InputFormat>("input-format").copied(),
+        // safe to unwrap because the argument has a default value
+        delimiter: *matches.get_one::<u8>("delimiter").unwrap(),
+        ..RunOptions::default()
+    })
+}
+
+fn store_kind(matches: &ArgMatches) -> Result<StoreKind, Box<dyn Error>> {
+    let path = matches.get_one::<PathBuf>("store-path").cloned();
+
+    match matches.get_one::<String>("store").map(String::as_str) {
+        Some("disk") => Ok(StoreKind::Disk(path)),
+        Some("sqlite") => match path {
+            Some(path) => Ok(StoreKind::Sqlite(path)),
+            None => Err("--store sqlite requires --store-path".into()),
+        },
+        // a memory store would silently keep nothing in the file
+        _ => match path {
+            Some(_) => Err("--store-path requires --store disk or --store sqlite".into()),
+            None => Ok(StoreKind::Memory),
+        },
+    }
+}
@@ -0,0 +1,212 @@
+use crate::common::types::{ClientId, TransactionType};
+use crate::stores::account_store::AccountStore;
+use crate::stores::store_error::StoreError;
+use crate::stores::transaction_store::{DisputeState, TransactionStore};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AuditFinding {
+    #[error("Client {0} has {1} {3} but its transactions add up to {2}")]
+    BalanceMismatch(ClientId, &'static str, Decimal, Decimal),
+
+    #[error("Client {0} has a negative held amount of {1}")]
+    NegativeHeld(ClientId, Decimal),
+
+    #[error("Client {0} has transactions but no account")]
+    MissingAccount(ClientId),
+
+    #[error("Client {0} has transactions that add up to more than a balance can hold")]
+    Overflow(ClientId),
+}
+
+#[derive(Default)]
+struct ExpectedBalances {
+    available: Decimal,
+    held: Decimal,
+    overflowed: bool,
+}
+
+impl ExpectedBalances {
+    // the history is summed in store order, so even a valid history can overflow on the way
+    fn apply(&mut self, available: Decimal, held: Decimal) {
+        match (
+            self.available.checked_add(available),
+            self.held.checked_add(held),
+        ) {
+            (Some(available), Some(held)) => {
+                self.available = available;
+                self.held = held;
+            }
+            _ => self.overflowed = true,
+        }
+    }
+}
+
+/// Recomputes every client's balances from the transaction history and its dispute states
+/// and compares them with the account store. An empty result means the stores agree.
+pub fn audit<A: AccountStore, T: TransactionStore>(
+    client_db: &A,
+    tx_history: &mut T,
+) -> Result<Vec<AuditFinding>, StoreError> {
+    let mut expected: BTreeMap<ClientId, ExpectedBalances> = BTreeMap::new();
+
+    for entry in tx_history.iter()? {
+        let (_, record) = entry?;
+        let tx = record.get_transaction();
+        // only accepted deposits and withdrawals are kept, and those always have an amount
+        let amount = tx.get_amount().unwrap_or_default();
+        let balances = expected.entry(tx.get_client_id()).or_default();
+
+        match (tx.get_transaction_type(), record.get_dispute_state()) {
+            (TransactionType::Deposit, DisputeState::Undisputed) => {
+                balances.apply(amount, Decimal::ZERO)
+            }
+            (TransactionType::Deposit, DisputeState::Disputed) => {
+                balances.apply(Decimal::ZERO, amount)
+            }
+            (TransactionType::Withdrawal, DisputeState::Undisputed) => {
+                balances.apply(-amount, Decimal::ZERO)
+            }
+            (TransactionType::Withdrawal, DisputeState::Disputed) => {
+                balances.apply(-amount, amount)
+            }
+            // a charged back transaction no longer counts towards the account
+            _ => {}
+        }
+    }
+
+    let mut clients = client_db.iter()?.collect::<Result<Vec<_>, StoreError>>()?;
+    clients.sort_by_key(|client| client.get_id());
+
+    let mut findings = Vec::new();
+    for client in clients {
+        let client_id = client.get_id();
+        let balances = expected.remove(&client_id).unwrap_or_default();
+        let snapshot = client.get_snapshot();
+
+        let comparisons = match balances.available.checked_add(balances.held) {
+            Some(total) if !balances.overflowed => vec![
+                ("available", balances.available, snapshot.get_available()),
+                ("held", balances.held, snapshot.get_held()),
+                ("total", total, snapshot.get_total()),
+            ],
+            _ => {
+                findings.push(AuditFinding::Overflow(client_id));
+                Vec::new()
+            }
+        };
+        for (field, expected, actual) in comparisons {
+            if expected != actual {
+                findings.push(AuditFinding::BalanceMismatch(
+                    client_id, field, expected, actual,
+                ));
+            }
+        }
+
+        if snapshot.get_held() < Decimal::ZERO {
+            findings.push(AuditFinding::NegativeHeld(client_id, snapshot.get_held()));
+        }
+    }
+
+    findings.extend(expected.into_keys().map(AuditFinding::MissingAccount));
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::TransactionType::{Deposit, Withdrawal};
+    use crate::common::types::{tx_id, TransactionId};
+    use crate::models::client::Client;
+    use crate::models::transaction::Transaction;
+    use crate::stores::account_store::MemoryAccountStore;
+    use crate::stores::transaction_store::{MemoryTransactionStore, TransactionRecord};
+    use rust_decimal_macros::dec;
+
+    fn record(
+        tx_id: TransactionId,
+        tx_type: TransactionType,
+        amount: Decimal,
+    ) -> TransactionRecord {
+        TransactionRecord::new(Transaction::new(tx_id, tx_type, 1, Some(amount)))
+    }
+
+    fn history() -> MemoryTransactionStore {
+        let mut tx_history = MemoryTransactionStore::new();
+        let mut disputed = record(tx_id(2), Deposit, dec!(2.0));
+        disputed.set_dispute_state(DisputeState::Disputed);
+        let mut charged_back = record(tx_id(3), Deposit, dec!(4.0));
+        charged_back.set_dispute_state(DisputeState::ChargedBack);
+
+        tx_history
+            .upsert((tx_id(1), None), record(tx_id(1), Deposit, dec!(5.0)))
+            .unwrap();
+        tx_history.upsert((tx_id(2), None), disputed).unwrap();
+        tx_history.upsert((tx_id(3), None), charged_back).unwrap();
+        tx_history
+            .upsert((tx_id(4), None), record(tx_id(4), Withdrawal, dec!(1.5)))
+            .unwrap();
+        tx_history
+    }
+
+    #[test]
+    fn test_consistent_stores_have_no_findings() {
+        let mut client_db = MemoryAccountStore::new();
+        client_db
+            .upsert(Client::restore(1, dec!(3.5), dec!(2.0), false))
+            .unwrap();
+        client_db.upsert(Client::new(2)).unwrap();
+
+        let findings = audit(&client_db, &mut history()).unwrap();
+
+        assert_eq!(findings, vec![]);
+    }
+
+    #[test]
+    fn test_discrepancies_are_reported() {
+        let mut client_db = MemoryAccountStore::new();
+        client_db
+            .upsert(Client::restore(1, dec!(3.5), dec!(-1.0), true))
+            .unwrap();
+
+        let mut tx_history = history();
+        let other_client = Transaction::new(tx_id(5), Deposit, 2, Some(dec!(1.0)));
+        tx_history
+            .upsert((tx_id(5), None), TransactionRecord::new(other_client))
+            .unwrap();
+
+        let findings = audit(&client_db, &mut tx_history).unwrap();
+
+        assert_eq!(
+            findings,
+            vec![
+                AuditFinding::BalanceMismatch(1, "held", dec!(2.0), dec!(-1.0)),
+                AuditFinding::BalanceMismatch(1, "total", dec!(5.5), dec!(2.5)),
+                AuditFinding::NegativeHeld(1, dec!(-1.0)),
+                AuditFinding::MissingAccount(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_history_that_does_not_fit_is_reported() {
+        let mut client_db = MemoryAccountStore::new();
+        client_db
+            .upsert(Client::restore(1, Decimal::MAX, dec!(0), false))
+            .unwrap();
+        let mut tx_history = MemoryTransactionStore::new();
+        tx_history
+            .upsert((tx_id(1), None), record(tx_id(1), Deposit, Decimal::MAX))
+            .unwrap();
+        tx_history
+            .upsert((tx_id(2), None), record(tx_id(2), Deposit, Decimal::MAX))
+            .unwrap();
+
+        let findings = audit(&client_db, &mut tx_history).unwrap();
+
+        assert_eq!(findings, vec![AuditFinding::Overflow(1)]);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::common::types::{TransactionId, TransactionType};
+use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::journal_entry::{JournalEntry, LedgerAccount, Posting, Side};
+use crate::models::ledger_event::{LedgerEvent, LedgerEventKind};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum JournalError {
+    #[error("Journal entry for transaction {0} does not balance")]
+    UnbalancedEntry(TransactionId),
+
+    #[error("Ledger account {0} is {1} but the client account says {2}")]
+    AccountMismatch(LedgerAccount, Decimal, Decimal),
+
+    #[error("Ledger account {0} would overflow")]
+    Overflow(LedgerAccount),
+
+    #[error("Trial balance totals would overflow")]
+    TotalOverflow,
+}
+
+/**
+ * Journal keeps a double-entry view of the client accounts. Every ledger event is posted
+ * as a balanced journal entry between client, settlement and loss accounts.
+ */
+#[derive(Debug, Default)]
+pub struct Journal {
+    // debits minus credits per account
+    balances: BTreeMap<LedgerAccount, Decimal>,
+    entries: u64,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal::default()
+    }
+
+    /// The journal entry for an event, or `None` if the event moves no money.
+    pub fn entry_for(event: &LedgerEvent) -> Option<JournalEntry> {
+        let client_id = event.get_client_id();
+        let available = LedgerAccount::ClientAvailable(client_id);
+        let held = LedgerAccount::ClientHeld(client_id);
+
+        let postings = match event.get_kind() {
+            LedgerEventKind::AccountOpened | LedgerEventKind::AccountLocked => return None,
+            LedgerEventKind::Deposited { amount } => vec![
+                Posting::debit(LedgerAccount::ExternalSettlement, *amount),
+                Posting::credit(available, *amount),
+            ],
+            LedgerEventKind::Withdrawn { amount } => vec![
+                Posting::debit(available, *amount),
+                Posting::credit(LedgerAccount::ExternalSettlement, *amount),
+            ],
+            LedgerEventKind::FundsHeld {
+                amount,
+                disputed_type,
+            } => match disputed_type {
+                TransactionType::Withdrawal => vec![
+                    // the disputed withdrawal may have to be paid back
+                    Posting::debit(LedgerAccount::ChargebackLoss, *amount),
+                    Posting::credit(held, *amount),
+                ],
+                _ => vec![
+                    Posting::debit(available, *amount),
+                    Posting::credit(held, *amount),
+                ],
+            },
+            LedgerEventKind::FundsReleased {
+                amount,
+                disputed_type,
+            } => match disputed_type {
+                TransactionType::Withdrawal => vec![
+                    Posting::debit(held, *amount),
+                    Posting::credit(LedgerAccount::ChargebackLoss, *amount),
+                ],
+                _ => vec![
+                    Posting::debit(held, *amount),
+                    Posting::credit(available, *amount),
+                ],
+            },
+            LedgerEventKind::ChargedBack {
+                amount,
+                disputed_type,
+            } => match disputed_type {
+                TransactionType::Withdrawal => vec![
+                    Posting::debit(held, *amount),
+                    Posting::credit(available, *amount),
+                ],
+                _ => vec![
+                    // the deposit goes back to where it came from
+                    Posting::debit(held, *amount),
+                    Posting::credit(LedgerAccount::ExternalSettlement, *amount),
+                ],
+            },
+        };
+
+        Some(JournalEntry::new(
+            event.get_offset(),
+            event.get_transaction_id(),
+            postings,
+        ))
+    }
+
+    pub fn post_event(&mut self, event: &LedgerEvent) -> Result<(), JournalError> {
+        match Journal::entry_for(event) {
+            Some(entry) => self.post(&entry),
+            None => Ok(()),
+        }
+    }
+
+    pub fn post(&mut self, entry: &JournalEntry) -> Result<(), JournalError> {
+        if !entry.is_balanced() {
+            return Err(JournalError::UnbalancedEntry(entry.get_transaction_id()));
+        }
+
+        // every posting is checked before any is applied, so a failed entry changes nothing
+        let mut updated: Vec<(&LedgerAccount, Decimal)> = Vec::new();
+        for posting in entry.get_postings() {
+            let account = posting.get_account();
+            let balance = updated
+                .iter()
+                .rev()
+                .find(|(updated_account, _)| *updated_account == account)
+                .map(|(_, balance)| *balance)
+                .or_else(|| self.balances.get(account).copied())
+                .unwrap_or(Decimal::ZERO);
+            let balance = match posting.get_side() {
+                Side::Debit => balance.checked_add(posting.get_amount()),
+                Side::Credit => balance.checked_sub(posting.get_amount()),
+            };
+            match balance {
+                Some(balance) => updated.push((account, balance)),
+                None => return Err(JournalError::Overflow(account.clone())),
+            }
+        }
+        for (account, balance) in updated {
+            self.balances.insert(account.clone(), balance);
+        }
+        self.entries += 1;
+
+        Ok(())
+    }
+
+    pub fn get_entry_count(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn trial_balance(&self) -> TrialBalance {
+        let lines = self
+            .balances
+            .iter()
+            .map(|(account, balance)| {
+                let (debit, credit) = if balance.is_sign_negative() {
+                    (Decimal::ZERO, -*balance)
+                } else {
+                    (*balance, Decimal::ZERO)
+                };
+                TrialBalanceLine {
+                    account: account.clone(),
+                    debit,
+                    credit,
+                }
+            })
+            .collect();
+
+        TrialBalance { lines }
+    }
+
+    /// Checks that the client ledger accounts agree with the client balances.
+    pub fn reconcile(&self, clients: &[ClientSnapshot]) -> Vec<JournalError> {
+        let mut errors = Vec::new();
+
+        for client in clients {
+            let accounts = [
+                (
+                    LedgerAccount::ClientAvailable(client.get_id()),
+                    client.get_available(),
+                ),
+                (
+                    LedgerAccount::ClientHeld(client.get_id()),
+                    client.get_held(),
+                ),
+            ];
+            for (account, expected) in accounts {
+                // client accounts carry credit balances
+                let balance = -self.balances.get(&account).copied().unwrap_or_default();
+                if balance != expected {
+                    errors.push(JournalError::AccountMismatch(account, balance, expected));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrialBalanceLine {
+    pub account: LedgerAccount,
+    pub debit: Decimal,
+    pub credit: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrialBalance {
+    pub lines: Vec<TrialBalanceLine>,
+}
+
+impl TrialBalance {
+    pub fn total_debit(&self) -> Result<Decimal, JournalError> {
+        total(self.lines.iter().map(|line| line.debit))
+    }
+
+    pub fn total_credit(&self) -> Result<Decimal, JournalError> {
+        total(self.lines.iter().map(|line| line.credit))
+    }
+
+    pub fn is_balanced(&self) -> Result<bool, JournalError> {
+        Ok(self.total_debit()? == self.total_credit()?)
+    }
+}
+
+// every account may be near the limit of a decimal, so their sum can overflow
+fn total(mut amounts: impl Iterator<Item = Decimal>) -> Result<Decimal, JournalError> {
+    amounts.try_fold(Decimal::ZERO, |total, amount| {
+        total.checked_add(amount).ok_or(JournalError::TotalOverflow)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::tx_id;
+    use crate::managers::transaction_manager::TransactionManager;
+    use crate::models::transaction::Transaction;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_books_balance_after_disputes() {
+        let mut manager = TransactionManager::new().with_journal();
+        let transactions = vec![
+            Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(dec!(10.0))),
+            Transaction::new(tx_id(2), TransactionType::Withdrawal, 1, Some(dec!(4.0))),
+            Transaction::new(tx_id(2), TransactionType::Dispute, 1, None),
+            Transaction::new(tx_id(2), TransactionType::Chargeback, 1, None),
+            Transaction::new(tx_id(3), TransactionType::Deposit, 2, Some(dec!(5.0))),
+            Transaction::new(tx_id(3), TransactionType::Dispute, 2, None),
+        ];
+        for tx in transactions {
+            assert_eq!(manager.add_transaction(tx), Ok(()));
+        }
+
+        let journal = manager.take_journal().unwrap();
+        let trial_balance = journal.trial_balance();
+        let clients = manager.get_all_values().unwrap();
+
+        assert_eq!(trial_balance.is_balanced(), Ok(true));
+        assert_eq!(trial_balance.total_debit(), Ok(dec!(15.0)));
+        assert_eq!(journal.reconcile(&clients), vec![]);
+
+        let loss = trial_balance
+            .lines
+            .iter()
+            .find(|line| line.account == LedgerAccount::ChargebackLoss)
+            .unwrap();
+        assert_eq!(loss.debit, dec!(4.0));
+    }
+
+    #[test]
+    fn test_unbalanced_entry_is_rejected() {
+        let mut journal = Journal::new();
+        let entry = JournalEntry::new(
+            0,
+            tx_id(1),
+            vec![
+                Posting::debit(LedgerAccount::ExternalSettlement, dec!(1.0)),
+                Posting::credit(LedgerAccount::ClientAvailable(1), dec!(0.5)),
+            ],
+        );
+
+        assert_eq!(
+            journal.post(&entry),
+            Err(JournalError::UnbalancedEntry(tx_id(1)))
+        );
+        assert_eq!(journal.get_entry_count(), 0);
+    }
+
+    #[test]
+    fn test_trial_balance_total_overflow() {
+        let mut manager = TransactionManager::new().with_journal();
+        let transactions = vec![
+            Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(Decimal::MAX)),
+            Transaction::new(tx_id(2), TransactionType::Withdrawal, 1, Some(Decimal::MAX)),
+            Transaction::new(tx_id(2), TransactionType::Dispute, 1, None),
+            Transaction::new(tx_id(3), TransactionType::Deposit, 2, Some(Decimal::MAX)),
+        ];
+        for tx in transactions {
+            assert_eq!(manager.add_transaction(tx), Ok(()));
+        }
+
+        let trial_balance = manager.take_journal().unwrap().trial_balance();
+
+        assert_eq!(
+            trial_balance.total_debit(),
+            Err(JournalError::TotalOverflow)
+        );
+        assert_eq!(
+            trial_balance.total_credit(),
+            Err(JournalError::TotalOverflow)
+        );
+        assert_eq!(
+            trial_balance.is_balanced(),
+            Err(JournalError::TotalOverflow)
+        );
+    }
+}
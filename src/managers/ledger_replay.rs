@@ -0,0 +1,144 @@
+use crate::common::types::ClientId;
+use crate::managers::transaction_manager::TxError;
+use crate::models::client::Client;
+use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::ledger_event::{LedgerEvent, LedgerEventKind};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    #[error("Event {0} cannot be replayed: {1}")]
+    InvalidEvent(u64, TxError),
+}
+
+/// Rebuilds the client accounts from a prefix of the event log,
+/// e.g. `replay(&events[..offset])`. Fails on an event the account cannot take, which means
+/// the log was not recorded by the engine or has been altered.
+pub fn replay<'a, I>(events: I) -> Result<Vec<ClientSnapshot>, ReplayError>
+where
+    I: IntoIterator<Item = &'a LedgerEvent>,
+{
+    let mut clients: HashMap<ClientId, Client> = HashMap::new();
+
+    for event in events {
+        let client_id = event.get_client_id();
+        let client = clients
+            .entry(client_id)
+            .or_insert_with(|| Client::new(client_id));
+
+        let result = match event.get_kind() {
+            LedgerEventKind::AccountOpened => Ok(()),
+            LedgerEventKind::Deposited { amount } => client.deposit(*amount),
+            LedgerEventKind::Withdrawn { amount } => client.withdraw(*amount),
+            LedgerEventKind::FundsHeld {
+                amount,
+                disputed_type,
+            } => client.dispute(*amount, disputed_type.clone()),
+            LedgerEventKind::FundsReleased {
+                amount,
+                disputed_type,
+            } => client.resolve(*amount, disputed_type.clone()),
+            LedgerEventKind::ChargedBack {
+                amount,
+                disputed_type,
+            } => client.chargeback(*amount, disputed_type.clone()),
+            LedgerEventKind::AccountLocked => {
+                client.freeze();
+                Ok(())
+            }
+        };
+        result.map_err(|err| ReplayError::InvalidEvent(event.get_offset(), err))?;
+    }
+
+    Ok(clients
+        .values()
+        .map(|client| client.get_snapshot())
+        .collect())
+}
+
+/// Rebuilds the client accounts as they were after the transaction at `position`
+/// (1-based, counting rejected transactions too) had been processed.
+pub fn replay_until_position(
+    events: &[LedgerEvent],
+    position: u64,
+) -> Result<Vec<ClientSnapshot>, ReplayError> {
+    replay(
+        events
+            .iter()
+            .take_while(|event| event.get_position() <= position),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::{tx_id, TransactionType};
+    use crate::managers::transaction_manager::TransactionManager;
+    use crate::models::transaction::Transaction;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn find(snapshots: &[ClientSnapshot], client_id: ClientId) -> &ClientSnapshot {
+        snapshots.iter().find(|s| s.get_id() == client_id).unwrap()
+    }
+
+    fn assert_snapshot(snapshot: &ClientSnapshot, available: Decimal, held: Decimal, locked: bool) {
+        assert_eq!(snapshot.get_available(), available);
+        assert_eq!(snapshot.get_held(), held);
+        assert_eq!(snapshot.get_locked(), locked);
+    }
+
+    #[test]
+    fn test_replay_to_any_position() {
+        let mut manager = TransactionManager::new().with_event_log();
+        let transactions = vec![
+            Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(dec!(10.0))),
+            Transaction::new(tx_id(2), TransactionType::Withdrawal, 1, Some(dec!(20.0))),
+            Transaction::new(tx_id(3), TransactionType::Deposit, 2, Some(dec!(5.0))),
+            Transaction::new(tx_id(1), TransactionType::Dispute, 1, None),
+            Transaction::new(tx_id(1), TransactionType::Chargeback, 1, None),
+        ];
+        for tx in transactions {
+            let _ = manager.add_transaction(tx);
+        }
+        let events = manager.take_events();
+
+        // the rejected withdrawal leaves no event behind
+        assert_eq!(events.len(), 7);
+        assert_eq!(events[6].get_kind(), &LedgerEventKind::AccountLocked);
+
+        let after_first = replay_until_position(&events, 1).unwrap();
+        assert_eq!(after_first.len(), 1);
+        assert_snapshot(find(&after_first, 1), dec!(10.0), dec!(0), false);
+
+        let after_dispute = replay_until_position(&events, 4).unwrap();
+        assert_snapshot(find(&after_dispute, 1), dec!(0), dec!(10.0), false);
+        assert_snapshot(find(&after_dispute, 2), dec!(5.0), dec!(0), false);
+
+        let at_end = replay(&events).unwrap();
+        assert_snapshot(find(&at_end, 1), dec!(0), dec!(0), true);
+
+        // replaying a prefix of events
+        let first_two = replay(&events[..2]).unwrap();
+        assert_snapshot(find(&first_two, 1), dec!(10.0), dec!(0), false);
+    }
+
+    #[test]
+    fn test_replay_altered_log() {
+        let mut manager = TransactionManager::new().with_event_log();
+        let tx = Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(dec!(10.0)));
+        manager.add_transaction(tx).unwrap();
+        let mut events = manager.take_events();
+        // a withdrawal the account could never have paid
+        let kind = LedgerEventKind::Withdrawn { amount: dec!(20.0) };
+        events.push(LedgerEvent::new(2, 2, 1, tx_id(2), kind));
+
+        let result = replay(&events);
+
+        assert_eq!(
+            result,
+            Err(ReplayError::InvalidEvent(2, TxError::InsufficientFunds(1)))
+        );
+    }
+}
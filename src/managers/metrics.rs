@@ -0,0 +1,337 @@
+use crate::common::types::TransactionType;
+use crate::managers::transaction_manager::TxError;
+use crate::models::client_snapshot::ClientSnapshot;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+// upper bounds in seconds; a row takes microseconds, a store round trip milliseconds
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.000_001,
+    0.000_002_5,
+    0.000_005,
+    0.000_01,
+    0.000_025,
+    0.000_05,
+    0.000_1,
+    0.000_25,
+    0.000_5,
+    0.001,
+    0.01,
+    0.1,
+];
+
+/**
+ * Histogram counts durations into the fixed `LATENCY_BUCKETS`, the way a Prometheus histogram
+ * does. Every bucket counts the observations up to its bound, so the last one counts them all.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    // observations per bucket, not yet cumulative; the last entry is above every bound
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn get_sum(&self) -> f64 {
+        self.sum
+    }
+
+    fn write<W: Write>(&self, writer: &mut W, name: &str) -> io::Result<()> {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += count;
+            writeln!(writer, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative)?;
+        }
+        writeln!(writer, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count)?;
+        writeln!(writer, "{}_sum {}", name, self.sum)?;
+        writeln!(writer, "{}_count {}", name, self.count)
+    }
+}
+
+/**
+ * Metrics counts what a run did: the rows read, the transactions offered to the ledger by type
+ * and outcome, the rejections by `TxError` kind, the disputes left open and the locked accounts,
+ * along with how long parsing a row and processing a transaction took. `write_prometheus` writes
+ * them in the Prometheus text format.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    rows: u64,
+    invalid_rows: u64,
+    // (type, accepted) to count
+    transactions: BTreeMap<(&'static str, bool), u64>,
+    rejections: BTreeMap<&'static str, u64>,
+    // disputes opened during the run and not resolved or charged back since
+    open_disputes: u64,
+    accounts: u64,
+    locked_accounts: u64,
+    parse_latency: Histogram,
+    process_latency: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Counts a row read from an input and how long reading and parsing it took.
+    pub fn observe_row(&mut self, valid: bool, duration: Duration) {
+        self.rows += 1;
+        if !valid {
+            self.invalid_rows += 1;
+        }
+        self.parse_latency.observe(duration);
+    }
+
+    /// Counts a transaction offered to the ledger, what came of it and how long it took.
+    pub fn observe_transaction(
+        &mut self,
+        tx_type: &TransactionType,
+        result: &Result<(), TxError>,
+        duration: Duration,
+    ) {
+        let accepted = result.is_ok();
+        *self
+            .transactions
+            .entry((tx_type.as_str(), accepted))
+            .or_insert(0) += 1;
+
+        if let Err(err) = result {
+            *self.rejections.entry(err.kind()).or_insert(0) += 1;
+        }
+        self.process_latency.observe(duration);
+    }
+
+    /// Takes the number of accounts and locked accounts from the final balances.
+    /// Takes the disputes still open in the transaction store, including earlier runs' ones.
+    pub fn observe_open_disputes(&mut self, open_disputes: u64) {
+        self.open_disputes = open_disputes;
+    }
+
+    pub fn observe_accounts(&mut self, clients: &[ClientSnapshot]) {
+        self.accounts = clients.len() as u64;
+        self.locked_accounts = clients.iter().filter(|client| client.get_locked()).count() as u64;
+    }
+
+    pub fn get_rows(&self) -> u64 {
+        self.rows
+    }
+
+    pub fn get_invalid_rows(&self) -> u64 {
+        self.invalid_rows
+    }
+
+    pub fn get_transactions(&self, tx_type: &TransactionType, accepted: bool) -> u64 {
+        let key = (tx_type.as_str(), accepted);
+        self.transactions.get(&key).copied().unwrap_or(0)
+    }
+
+    pub fn get_rejections(&self, kind: &str) -> u64 {
+        self.rejections.get(kind).copied().unwrap_or(0)
+    }
+
+    pub fn get_open_disputes(&self) -> u64 {
+        self.open_disputes
+    }
+
+    pub fn get_locked_accounts(&self) -> u64 {
+        self.locked_accounts
+    }
+
+    pub fn get_parse_latency(&self) -> &Histogram {
+        &self.parse_latency
+    }
+
+    pub fn get_process_latency(&self) -> &Histogram {
+        &self.process_latency
+    }
+
+    /// Writes every metric in the Prometheus text exposition format.
+    pub fn write_prometheus<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_header(
+            &mut writer,
+            "ledger_rows_total",
+            "counter",
+            "Input rows read.",
+        )?;
+        writeln!(writer, "ledger_rows_total {}", self.rows)?;
+        write_header(
+            &mut writer,
+            "ledger_invalid_rows_total",
+            "counter",
+            "Input rows that could not be read as a transaction.",
+        )?;
+        writeln!(writer, "ledger_invalid_rows_total {}", self.invalid_rows)?;
+
+        write_header(
+            &mut writer,
+            "ledger_transactions_total",
+            "counter",
+            "Transactions offered to the ledger by type and outcome.",
+        )?;
+        for ((tx_type, accepted), count) in self.transactions.iter() {
+            let outcome = if *accepted { "accepted" } else { "rejected" };
+            writeln!(
+                writer,
+                "ledger_transactions_total{{type=\"{}\",outcome=\"{}\"}} {}",
+                tx_type, outcome, count
+            )?;
+        }
+
+        write_header(
+            &mut writer,
+            "ledger_rejections_total",
+            "counter",
+            "Transactions the ledger rejected by reason.",
+        )?;
+        for (kind, count) in self.rejections.iter() {
+            writeln!(
+                writer,
+                "ledger_rejections_total{{reason=\"{}\"}} {}",
+                kind, count
+            )?;
+        }
+
+        write_header(
+            &mut writer,
+            "ledger_open_disputes",
+            "gauge",
+            "Transactions under dispute in the transaction store.",
+        )?;
+        writeln!(writer, "ledger_open_disputes {}", self.open_disputes)?;
+        write_header(&mut writer, "ledger_accounts", "gauge", "Client accounts.")?;
+        writeln!(writer, "ledger_accounts {}", self.accounts)?;
+        write_header(
+            &mut writer,
+            "ledger_locked_accounts",
+            "gauge",
+            "Client accounts locked by a chargeback.",
+        )?;
+        writeln!(writer, "ledger_locked_accounts {}", self.locked_accounts)?;
+
+        write_header(
+            &mut writer,
+            "ledger_parse_seconds",
+            "histogram",
+            "Time taken to read and parse an input row.",
+        )?;
+        self.parse_latency
+            .write(&mut writer, "ledger_parse_seconds")?;
+        write_header(
+            &mut writer,
+            "ledger_process_seconds",
+            "histogram",
+            "Time taken to process a transaction.",
+        )?;
+        self.process_latency
+            .write(&mut writer, "ledger_process_seconds")?;
+
+        writer.flush()
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W, name: &str, kind: &str, help: &str) -> io::Result<()> {
+    writeln!(writer, "# HELP {} {}", name, help)?;
+    writeln!(writer, "# TYPE {} {}", name, kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    #[rstest]
+    #[case(Duration::from_nanos(500), 0)]
+    #[case(Duration::from_micros(1), 0)]
+    #[case(Duration::from_micros(3), 2)]
+    #[case(Duration::from_millis(5), 10)]
+    #[case(Duration::from_secs(1), 12)]
+    fn test_histogram_bucket(#[case] duration: Duration, #[case] bucket: usize) {
+        let mut histogram = Histogram::default();
+
+        histogram.observe(duration);
+
+        assert_eq!(histogram.buckets[bucket], 1);
+        assert_eq!(histogram.get_count(), 1);
+    }
+
+    #[test]
+    fn test_observe_transaction() {
+        // Arrange
+        let mut metrics = Metrics::new();
+        let took = Duration::from_micros(2);
+
+        // Act
+        metrics.observe_transaction(&TransactionType::Deposit, &Ok(()), took);
+        metrics.observe_transaction(&TransactionType::Dispute, &Ok(()), took);
+        metrics.observe_transaction(&TransactionType::Dispute, &Ok(()), took);
+        metrics.observe_transaction(&TransactionType::Resolve, &Ok(()), took);
+        let rejected = Err(TxError::InsufficientFunds(1));
+        metrics.observe_transaction(&TransactionType::Withdrawal, &rejected, took);
+
+        // Assert
+        assert_eq!(metrics.get_transactions(&TransactionType::Dispute, true), 2);
+        assert_eq!(
+            metrics.get_transactions(&TransactionType::Withdrawal, false),
+            1
+        );
+        assert_eq!(
+            metrics.get_transactions(&TransactionType::Withdrawal, true),
+            0
+        );
+        assert_eq!(metrics.get_rejections("insufficient_funds"), 1);
+        assert_eq!(metrics.get_process_latency().get_count(), 5);
+    }
+
+    #[test]
+    fn test_write_prometheus() {
+        // Arrange
+        let mut metrics = Metrics::new();
+        metrics.observe_row(true, Duration::from_micros(3));
+        metrics.observe_row(false, Duration::from_micros(3));
+        let rejected = Err(TxError::ClientFrozen(2));
+        metrics.observe_transaction(&TransactionType::Deposit, &rejected, Duration::ZERO);
+        metrics.observe_accounts(&[
+            ClientSnapshot::new(1, Decimal::ONE, Decimal::ZERO, false),
+            ClientSnapshot::new(2, Decimal::ZERO, Decimal::ZERO, true),
+        ]);
+        let mut output = Vec::new();
+
+        // Act
+        metrics.write_prometheus(&mut output).unwrap();
+
+        // Assert
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.contains(&"# TYPE ledger_transactions_total counter"));
+        assert!(lines.contains(&"ledger_rows_total 2"));
+        assert!(lines.contains(&"ledger_invalid_rows_total 1"));
+        assert!(
+            lines.contains(&"ledger_transactions_total{type=\"deposit\",outcome=\"rejected\"} 1")
+        );
+        assert!(lines.contains(&"ledger_rejections_total{reason=\"client_frozen\"} 1"));
+        assert!(lines.contains(&"ledger_locked_accounts 1"));
+        assert!(lines.contains(&"ledger_parse_seconds_bucket{le=\"0.0000025\"} 0"));
+        assert!(lines.contains(&"ledger_parse_seconds_bucket{le=\"0.000005\"} 2"));
+        assert!(lines.contains(&"ledger_parse_seconds_bucket{le=\"+Inf\"} 2"));
+        assert!(lines.contains(&"ledger_process_seconds_count 1"));
+    }
+}
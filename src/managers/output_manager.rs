@@ -1,5 +1,14 @@
+#[cfg(feature = "parquet")]
+use crate::common::columnar::write_accounts_parquet;
+use crate::common::compression::{CompressedWriter, Compression};
+use crate::managers::journal::{JournalError, TrialBalance};
 use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::ledger_event::LedgerEvent;
+use crate::models::rejection::Rejection;
+use crate::models::transaction::Transaction;
+use crate::support::generator::GeneratedRow;
 use csv::Error as CsvError;
+use std::io::Write;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,6 +18,17 @@ pub enum OutputError {
 
     #[error("failed to write to stdout: {0}")]
     IoWriteError(#[from] std::io::Error),
+
+    #[error("failed to total the trial balance: {0}")]
+    TrialBalanceError(#[from] JournalError),
+
+    #[cfg(feature = "parquet")]
+    #[error("failed to write to Parquet: {0}")]
+    ParquetWriteError(#[from] parquet::errors::ParquetError),
+
+    #[cfg(feature = "parquet")]
+    #[error("failed to build the account columns: {0}")]
+    ArrowWriteError(#[from] arrow_schema::ArrowError),
 }
 
 pub trait OutputManager {
@@ -16,29 +36,209 @@ pub trait OutputManager {
     fn write_output(&self, clients: &[ClientSnapshot]) -> Result<(), OutputError>;
 }
 
-pub struct CsvOutputManager;
+pub struct CsvOutputManager {
+    compression: Compression,
+}
+
+impl CsvOutputManager {
+    /// Compresses the account file written to stdout.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+}
 
 impl OutputManager for CsvOutputManager {
     fn new() -> Self {
-        CsvOutputManager {}
+        CsvOutputManager {
+            compression: Compression::None,
+        }
+    }
+
+    fn write_output(&self, clients: &[ClientSnapshot]) -> Result<(), OutputError> {
+        let mut writer = CompressedWriter::new(std::io::stdout(), self.compression)?;
+        write_accounts(&mut writer, clients)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/**
+ * ParquetOutputManager writes the accounts to stdout as a Parquet file, with the balances as
+ * decimal columns of scale 4.
+ */
+#[cfg(feature = "parquet")]
+pub struct ParquetOutputManager;
+
+#[cfg(feature = "parquet")]
+impl OutputManager for ParquetOutputManager {
+    fn new() -> Self {
+        ParquetOutputManager
     }
 
     fn write_output(&self, clients: &[ClientSnapshot]) -> Result<(), OutputError> {
-        let mut wtr = csv::Writer::from_writer(std::io::stdout());
-        wtr.write_record(["client", "available", "held", "total", "locked"])?;
+        write_accounts_parquet(std::io::stdout(), clients)
+    }
+}
+
+pub fn write_accounts<W: Write>(writer: W, clients: &[ClientSnapshot]) -> Result<(), OutputError> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(["client", "available", "held", "total", "locked"])?;
+
+    for client in clients.iter() {
+        let client_id = client.get_id().to_string();
+        let available = client.get_available().to_string();
+        let held = client.get_held().to_string();
+        let total = client.get_total().to_string();
+        let locked = client.get_locked().to_string();
 
-        for client in clients.iter() {
-            let client_id = client.get_id().to_string();
-            let available = client.get_available().to_string();
-            let held = client.get_held().to_string();
-            let total = client.get_total().to_string();
-            let locked = client.get_locked().to_string();
+        wtr.write_record(&[client_id, available, held, total, locked])?;
+    }
+
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Writes transactions in the input format.
+pub fn write_transactions<W, I>(writer: W, transactions: I) -> Result<(), OutputError>
+where
+    W: Write,
+    I: IntoIterator<Item = Transaction>,
+{
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(["type", "client", "tx", "amount"])?;
+
+    for tx in transactions {
+        wtr.write_record(transaction_record(&tx))?;
+    }
 
-            wtr.write_record(&[client_id, available, held, total, locked])?;
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Writes generated rows like `write_transactions`, malformed rows as they are.
+pub fn write_rows<W, I>(writer: W, rows: I) -> Result<(), OutputError>
+where
+    W: Write,
+    I: IntoIterator<Item = GeneratedRow>,
+{
+    // malformed rows may have more or fewer fields
+    let mut wtr = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+    wtr.write_record(["type", "client", "tx", "amount"])?;
+
+    for row in rows {
+        match row {
+            GeneratedRow::Transaction(tx) => wtr.write_record(transaction_record(&tx))?,
+            GeneratedRow::Malformed(fields) => wtr.write_record(&fields)?,
         }
+    }
 
-        wtr.flush()?;
+    wtr.flush()?;
 
-        Ok(())
+    Ok(())
+}
+
+fn transaction_record(tx: &Transaction) -> [String; 4] {
+    [
+        tx.get_transaction_type().as_str().to_string(),
+        tx.get_client_id().to_string(),
+        tx.get_transaction_id().to_string(),
+        tx.get_amount()
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+    ]
+}
+
+/// Writes rejected transactions with the file and line they came from and the reason.
+pub fn write_rejections<W: Write>(writer: W, rejections: &[Rejection]) -> Result<(), OutputError> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(["file", "line", "type", "client", "tx", "amount", "error"])?;
+
+    for rejection in rejections.iter() {
+        let location = rejection.get_location();
+        let tx = rejection.get_transaction();
+        wtr.write_record(&[
+            location
+                .get_file()
+                .map(|file| file.display().to_string())
+                .unwrap_or_default(),
+            location.get_line().to_string(),
+            tx.get_transaction_type().as_str().to_string(),
+            tx.get_client_id().to_string(),
+            tx.get_transaction_id().to_string(),
+            tx.get_amount()
+                .map(|amount| amount.to_string())
+                .unwrap_or_default(),
+            rejection.get_error().to_string(),
+        ])?;
     }
+
+    wtr.flush()?;
+
+    Ok(())
+}
+
+pub fn write_events<W: Write>(writer: W, events: &[LedgerEvent]) -> Result<(), OutputError> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record([
+        "offset",
+        "position",
+        "client",
+        "tx",
+        "event",
+        "amount",
+        "disputed_type",
+    ])?;
+
+    for event in events.iter() {
+        let amount = event.get_amount().map(|amount| amount.to_string());
+        let disputed_type = event.get_disputed_type();
+
+        wtr.write_record(&[
+            event.get_offset().to_string(),
+            event.get_position().to_string(),
+            event.get_client_id().to_string(),
+            event.get_transaction_id().to_string(),
+            event.get_name().to_string(),
+            amount.unwrap_or_default(),
+            disputed_type
+                .map(|tx_type| tx_type.as_str().to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+
+    wtr.flush()?;
+
+    Ok(())
+}
+
+pub fn write_trial_balance<W: Write>(
+    writer: W,
+    trial_balance: &TrialBalance,
+) -> Result<(), OutputError> {
+    // totalled first, so an overflow does not leave a trial balance without its total
+    let total_debit = trial_balance.total_debit()?;
+    let total_credit = trial_balance.total_credit()?;
+
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(["account", "debit", "credit"])?;
+
+    for line in trial_balance.lines.iter() {
+        wtr.write_record(&[
+            line.account.to_string(),
+            line.debit.to_string(),
+            line.credit.to_string(),
+        ])?;
+    }
+    wtr.write_record(&[
+        "total".to_string(),
+        total_debit.to_string(),
+        total_credit.to_string(),
+    ])?;
+
+    wtr.flush()?;
+
+    Ok(())
 }
@@ -1,11 +1,21 @@
-use crate::common::types::{ClientId, TransactionId, TransactionType};
+use crate::common::types::{ClientId, Timestamp, TransactionId, TransactionType};
+use crate::managers::audit::{audit, AuditFinding};
+use crate::managers::journal::{Journal, JournalError};
+use crate::managers::metrics::Metrics;
 use crate::models::client::Client;
 use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::ledger_event::{LedgerEvent, LedgerEventKind};
 use crate::models::transaction::Transaction;
-use std::collections::{HashMap, HashSet};
+use crate::stores::account_store::{AccountStore, MemoryAccountStore};
+use crate::stores::store_error::StoreError;
+use crate::stores::transaction_store::{
+    DisputeState, MemoryTransactionStore, TransactionRecord, TransactionStore, TxKey,
+};
+use std::time::Instant;
 use thiserror::Error;
+use tracing::{debug, field, info, info_span};
 
-#[derive(Error, Debug, PartialEq)]
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum TxError {
     #[error("Client {0} is frozen")]
     ClientFrozen(ClientId),
@@ -24,75 +34,306 @@ pub enum TxError {
 
     #[error("Transaction {0} is not disputed")]
     TransactionNotDisputed(TransactionId),
+
+    #[error("Transaction {0} is already disputed")]
+    TransactionAlreadyDisputed(TransactionId),
+
+    #[error("Transaction {0} is too old to be disputed")]
+    DisputeWindowExpired(TransactionId),
+
+    #[error("Client {0} balance would overflow")]
+    Overflow(ClientId),
+
+    #[error("Transaction {0} does not belong to client {1}")]
+    ClientMismatch(TransactionId, ClientId),
+
+    #[error("Store is unavailable: {0}")]
+    StoreFailure(String),
+
+    #[error("Books do not balance: {0}")]
+    JournalFailure(String),
+}
+
+impl TxError {
+    /// A stable name for the kind of error, without the ids it carries.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TxError::ClientFrozen(_) => "client_frozen",
+            TxError::TransactionAlreadyHappened(_) => "transaction_already_happened",
+            TxError::InsufficientFunds(_) => "insufficient_funds",
+            TxError::NoAmount(_) => "no_amount",
+            TxError::TransactionNotFound(_) => "transaction_not_found",
+            TxError::TransactionNotDisputed(_) => "transaction_not_disputed",
+            TxError::TransactionAlreadyDisputed(_) => "transaction_already_disputed",
+            TxError::DisputeWindowExpired(_) => "dispute_window_expired",
+            TxError::Overflow(_) => "overflow",
+            TxError::ClientMismatch(_, _) => "client_mismatch",
+            TxError::StoreFailure(_) => "store_failure",
+            TxError::JournalFailure(_) => "journal_failure",
+        }
+    }
 }
 
-pub struct TransactionManager {
-    client_db: HashMap<ClientId, Client>,
-    tx_history: HashMap<(TransactionId, ClientId), Transaction>,
-    tx_disputed: HashSet<(TransactionId, ClientId)>,
+impl From<JournalError> for TxError {
+    fn from(err: JournalError) -> Self {
+        TxError::JournalFailure(err.to_string())
+    }
+}
+
+impl From<StoreError> for TxError {
+    fn from(err: StoreError) -> Self {
+        TxError::StoreFailure(err.to_string())
+    }
+}
+
+pub struct TransactionManager<A = MemoryAccountStore, T = MemoryTransactionStore> {
+    client_db: A,
+    tx_history: T,
+    dispute_window: Option<Timestamp>,
+    global_tx_ids: bool,
+    // number of transactions offered so far, including rejected ones
+    position: u64,
+    event_count: u64,
+    events: Option<Vec<LedgerEvent>>,
+    journal: Option<Journal>,
+    metrics: Option<Metrics>,
 }
 
 impl TransactionManager {
     pub fn new() -> Self {
+        TransactionManager::with_stores(MemoryAccountStore::new(), MemoryTransactionStore::new())
+    }
+}
+
+impl Default for TransactionManager {
+    fn default() -> Self {
+        TransactionManager::new()
+    }
+}
+
+impl<A: AccountStore, T: TransactionStore> TransactionManager<A, T> {
+    pub fn with_stores(client_db: A, tx_history: T) -> Self {
         TransactionManager {
-            client_db: HashMap::new(),
-            tx_history: HashMap::new(),
-            tx_disputed: HashSet::new(),
+            client_db,
+            tx_history,
+            dispute_window: None,
+            global_tx_ids: true,
+            position: 0,
+            event_count: 0,
+            events: None,
+            journal: None,
+            metrics: None,
         }
     }
 
-    pub fn add_transaction(&mut self, tx: Transaction) -> Result<(), TxError> {
-        let client_db = &mut self.client_db;
+    /// Rejects disputes filed more than `window` seconds after the disputed transaction.
+    /// Transactions without a timestamp can always be disputed.
+    pub fn with_dispute_window(mut self, window: Timestamp) -> Self {
+        self.dispute_window = Some(window);
+        self
+    }
 
-        let client_id = tx.get_client_id();
-        let tx_id = tx.get_transaction_id();
+    /// When disabled, transaction ids only have to be unique per client.
+    pub fn with_global_tx_ids(mut self, global_tx_ids: bool) -> Self {
+        self.global_tx_ids = global_tx_ids;
+        self
+    }
+
+    /// Records every accepted operation as a `LedgerEvent`.
+    pub fn with_event_log(mut self) -> Self {
+        self.events = Some(Vec::new());
+        self
+    }
+
+    /// Hands over the events recorded so far.
+    pub fn take_events(&mut self) -> Vec<LedgerEvent> {
+        match &mut self.events {
+            Some(events) => std::mem::take(events),
+            None => Vec::new(),
+        }
+    }
+
+    /// Posts every accepted operation to a double-entry `Journal`.
+    pub fn with_journal(mut self) -> Self {
+        self.journal = Some(Journal::new());
+        self
+    }
+
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
+    }
+
+    /// Counts every transaction by type and outcome and times its processing.
+    pub fn with_metrics(mut self) -> Self {
+        self.metrics = Some(Metrics::new());
+        self
+    }
+
+    /// The metrics so far, for the caller to add what happens outside the manager.
+    pub fn metrics_mut(&mut self) -> Option<&mut Metrics> {
+        self.metrics.as_mut()
+    }
+
+    pub fn take_metrics(&mut self) -> Option<Metrics> {
+        self.metrics.take()
+    }
+
+    fn record(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        kind: LedgerEventKind,
+    ) -> Result<(), TxError> {
+        if self.events.is_none() && self.journal.is_none() {
+            return Ok(());
+        }
+
+        let event = LedgerEvent::new(self.event_count, self.position, client_id, tx_id, kind);
+        if let Some(journal) = &mut self.journal {
+            // a shared ledger account can overflow on valid input; that only rejects this one
+            journal.post_event(&event).map_err(|err| match err {
+                JournalError::Overflow(_) => TxError::Overflow(client_id),
+                err => TxError::from(err),
+            })?;
+        }
+        self.event_count += 1;
+
+        if let Some(events) = &mut self.events {
+            events.push(event);
+        }
+
+        Ok(())
+    }
+
+    pub fn add_transaction(&mut self, tx: Transaction) -> Result<(), TxError> {
         let tx_type = tx.get_transaction_type();
-        let tx_amount = tx.get_amount();
+        let span = info_span!(
+            "add_transaction",
+            client = %tx.get_client_id(),
+            tx = %tx.get_transaction_id(),
+            r#type = tx_type.as_str(),
+            outcome = field::Empty,
+        );
+        let _entered = span.enter();
+
+        let started = self.metrics.is_some().then(Instant::now);
+        let result = self.process_transaction(tx);
+        if let (Some(metrics), Some(started)) = (&mut self.metrics, started) {
+            metrics.observe_transaction(&tx_type, &result, started.elapsed());
+        }
 
-        let client_txs = client_db.get(&client_id);
-        if client_txs.is_none() {
-            client_db.insert(client_id, Client::new(client_id));
+        match &result {
+            Ok(()) => {
+                span.record("outcome", "accepted");
+                debug!("transaction accepted");
+            }
+            Err(err) => {
+                span.record("outcome", err.kind());
+                info!(error = %err, "transaction rejected");
+            }
         }
-        let client = client_db.get_mut(&client_id).unwrap();
+        result
+    }
+
+    fn process_transaction(&mut self, tx: Transaction) -> Result<(), TxError> {
+        self.position += 1;
+        let client_id = tx.get_client_id();
+
+        // the account exists from its first transaction on, even if that one is rejected
+        let mut client = match self.client_db.get(client_id)? {
+            Some(client) => client,
+            None => {
+                let client = Client::new(client_id);
+                let tx_id = tx.get_transaction_id();
+                self.record(client_id, tx_id, LedgerEventKind::AccountOpened)?;
+                self.client_db.upsert(client.clone())?;
+                client
+            }
+        };
         if client.is_locked() {
             return Err(TxError::ClientFrozen(client_id));
         }
 
-        let id_pair = &(tx_id, client_id);
+        self.apply_transaction(&mut client, tx)?;
+        self.client_db.upsert(client)?;
+
+        Ok(())
+    }
+
+    fn apply_transaction(&mut self, client: &mut Client, tx: Transaction) -> Result<(), TxError> {
+        let client_id = tx.get_client_id();
+        let tx_id = tx.get_transaction_id();
+        let tx_type = tx.get_transaction_type();
+        let tx_amount = tx.get_amount();
+
+        let id_pair: TxKey = if self.global_tx_ids {
+            (tx_id, None)
+        } else {
+            (tx_id, Some(client_id))
+        };
 
         match tx_type {
             TransactionType::Deposit => {
                 // did transaction already happen?
-                if self.tx_history.contains_key(id_pair) {
+                if self.tx_history.contains(&id_pair)? {
                     return Err(TxError::TransactionAlreadyHappened(tx_id));
                 }
 
                 if let Some(amount) = tx_amount {
-                    client.deposit(amount);
-                    self.tx_history.insert(*id_pair, tx);
+                    client.deposit(amount)?;
+                    self.record(client_id, tx_id, LedgerEventKind::Deposited { amount })?;
+                    self.tx_history
+                        .upsert(id_pair, TransactionRecord::new(tx))?;
                 } else {
                     return Err(TxError::NoAmount(tx_id));
                 }
             }
             TransactionType::Withdrawal => {
                 // did transaction already happen?
-                if self.tx_history.contains_key(id_pair) {
+                if self.tx_history.contains(&id_pair)? {
                     return Err(TxError::TransactionAlreadyHappened(tx_id));
                 }
 
                 // check if the client has enough funds
                 if let Some(amount) = tx_amount {
                     client.withdraw(amount)?;
-                    self.tx_history.insert(*id_pair, tx);
+                    self.record(client_id, tx_id, LedgerEventKind::Withdrawn { amount })?;
+                    self.tx_history
+                        .upsert(id_pair, TransactionRecord::new(tx))?;
                 } else {
                     return Err(TxError::NoAmount(tx_id));
                 }
             }
             TransactionType::Dispute => {
-                if let Some(transaction_to_dispute) = self.tx_history.get(id_pair) {
+                if let Some(mut record) = self.tx_history.get(&id_pair)? {
+                    let transaction_to_dispute = record.get_transaction();
+                    if transaction_to_dispute.get_client_id() != client_id {
+                        return Err(TxError::ClientMismatch(tx_id, client_id));
+                    }
+                    if !record.get_dispute_state().can_dispute() {
+                        return Err(TxError::TransactionAlreadyDisputed(tx_id));
+                    }
+
+                    if let (Some(window), Some(disputed_at), Some(happened_at)) = (
+                        self.dispute_window,
+                        tx.get_timestamp(),
+                        transaction_to_dispute.get_timestamp(),
+                    ) {
+                        if disputed_at.saturating_sub(happened_at) > window {
+                            return Err(TxError::DisputeWindowExpired(tx_id));
+                        }
+                    }
+
                     if let Some(amount) = transaction_to_dispute.get_amount() {
-                        client.dispute(amount, transaction_to_dispute.get_transaction_type());
-                        self.tx_disputed.insert(*id_pair);
+                        let disputed_type = transaction_to_dispute.get_transaction_type();
+                        client.dispute(amount, disputed_type.clone())?;
+                        let kind = LedgerEventKind::FundsHeld {
+                            amount,
+                            disputed_type,
+                        };
+                        self.record(client_id, tx_id, kind)?;
+                        record.set_dispute_state(DisputeState::Disputed);
+                        self.tx_history.upsert(id_pair, record)?;
                     } else {
                         return Err(TxError::NoAmount(tx_id));
                     }
@@ -101,38 +342,63 @@ impl TransactionManager {
                 }
             }
             TransactionType::Resolve => {
-                // check if the transaction is disputed
-                if !(self.tx_disputed.contains(id_pair)) {
-                    return Err(TxError::TransactionNotDisputed(tx_id));
-                }
+                if let Some(mut record) = self.tx_history.get(&id_pair)? {
+                    // check if the transaction is disputed
+                    if !record.is_disputed() {
+                        return Err(TxError::TransactionNotDisputed(tx_id));
+                    }
+
+                    let transaction = record.get_transaction();
+                    if transaction.get_client_id() != client_id {
+                        return Err(TxError::ClientMismatch(tx_id, client_id));
+                    }
 
-                if let Some(transaction) = self.tx_history.get(id_pair) {
                     if let Some(amount) = transaction.get_amount() {
-                        client.resolve(amount, transaction.get_transaction_type());
-                        self.tx_disputed.remove(id_pair);
+                        let disputed_type = transaction.get_transaction_type();
+                        client.resolve(amount, disputed_type.clone())?;
+                        let kind = LedgerEventKind::FundsReleased {
+                            amount,
+                            disputed_type,
+                        };
+                        self.record(client_id, tx_id, kind)?;
+                        record.set_dispute_state(DisputeState::Undisputed);
+                        self.tx_history.upsert(id_pair, record)?;
                     } else {
                         return Err(TxError::NoAmount(tx_id));
                     }
                 } else {
-                    return Err(TxError::TransactionNotFound(tx_id));
+                    return Err(TxError::TransactionNotDisputed(tx_id));
                 }
             }
             TransactionType::Chargeback => {
-                // check if the transaction is disputed
-                if !(self.tx_disputed.contains(id_pair)) {
-                    return Err(TxError::TransactionNotDisputed(tx_id));
-                }
+                if let Some(mut record) = self.tx_history.get(&id_pair)? {
+                    // check if the transaction is disputed
+                    if !record.is_disputed() {
+                        return Err(TxError::TransactionNotDisputed(tx_id));
+                    }
+
+                    let transaction = record.get_transaction();
+                    if transaction.get_client_id() != client_id {
+                        return Err(TxError::ClientMismatch(tx_id, client_id));
+                    }
 
-                if let Some(transaction) = self.tx_history.get(id_pair) {
                     if let Some(amount) = transaction.get_amount() {
-                        client.chargeback(amount, transaction.get_transaction_type());
+                        let disputed_type = transaction.get_transaction_type();
+                        client.chargeback(amount, disputed_type.clone())?;
+                        let kind = LedgerEventKind::ChargedBack {
+                            amount,
+                            disputed_type,
+                        };
+                        self.record(client_id, tx_id, kind)?;
+                        self.record(client_id, tx_id, LedgerEventKind::AccountLocked)?;
                         client.freeze();
-                        self.tx_disputed.remove(id_pair);
+                        record.set_dispute_state(DisputeState::ChargedBack);
+                        self.tx_history.upsert(id_pair, record)?;
                     } else {
                         return Err(TxError::NoAmount(tx_id));
                     }
                 } else {
-                    return Err(TxError::TransactionNotFound(tx_id));
+                    return Err(TxError::TransactionNotDisputed(tx_id));
                 }
             }
         }
@@ -140,20 +406,52 @@ impl TransactionManager {
         Ok(())
     }
 
-    pub fn get_all_values(self) -> Vec<ClientSnapshot> {
+    pub fn get_snapshot(&self, client_id: ClientId) -> Result<Option<ClientSnapshot>, TxError> {
+        let client = self.client_db.get(client_id)?;
+        Ok(client.map(|client| client.get_snapshot()))
+    }
+
+    /// Checks the accounts against the transaction history, see `managers::audit`.
+    pub fn audit(&mut self) -> Result<Vec<AuditFinding>, TxError> {
+        Ok(audit(&self.client_db, &mut self.tx_history)?)
+    }
+
+    /// Transactions under dispute in the store, including those disputed in earlier runs.
+    pub fn count_open_disputes(&mut self) -> Result<u64, TxError> {
+        let mut open_disputes = 0;
+        for entry in self.tx_history.iter()? {
+            let (_, record) = entry?;
+            if record.is_disputed() {
+                open_disputes += 1;
+            }
+        }
+
+        Ok(open_disputes)
+    }
+
+    /// Commits what the stores have written, see `AccountStore::commit`.
+    pub fn commit(&mut self) -> Result<(), TxError> {
+        self.client_db.commit()?;
+        self.tx_history.commit()?;
+
+        Ok(())
+    }
+
+    pub fn get_all_values(self) -> Result<Vec<ClientSnapshot>, TxError> {
         let snapshots = self
             .client_db
-            .values()
-            .map(|xx| xx.get_snapshot())
-            .collect::<Vec<ClientSnapshot>>();
+            .iter()?
+            .map(|client| Ok(client?.get_snapshot()))
+            .collect::<Result<Vec<ClientSnapshot>, TxError>>()?;
 
-        snapshots
+        Ok(snapshots)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::types::tx_id;
     use crate::common::types::TransactionType::{Deposit, Dispute, Resolve, Withdrawal};
     use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
@@ -163,109 +461,284 @@ mod tests {
     fn test_input_multiple_clients_deposit_withdraw() {
         let mut manager = TransactionManager::new();
 
-        let tx1 = Tx::new(1, Deposit, 1, Some(dec!(1.0)));
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(1.0)));
         let res1 = manager.add_transaction(tx1);
         assert_eq!(res1, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(1.0), dec!(0));
+        assert_balance(&manager, 1, dec!(1.0), dec!(0));
 
-        let tx2 = Tx::new(2, Deposit, 2, Some(dec!(2.0)));
+        let tx2 = Tx::new(tx_id(2), Deposit, 2, Some(dec!(2.0)));
         let res2 = manager.add_transaction(tx2);
         assert_eq!(res2, Ok(()));
-        assert_balance(manager.client_db.get(&2).unwrap(), dec!(2.0), dec!(0));
+        assert_balance(&manager, 2, dec!(2.0), dec!(0));
 
-        let tx3 = Tx::new(3, Deposit, 1, Some(dec!(2.0)));
+        let tx3 = Tx::new(tx_id(3), Deposit, 1, Some(dec!(2.0)));
         let res3 = manager.add_transaction(tx3);
         assert_eq!(res3, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(3.0), dec!(0));
+        assert_balance(&manager, 1, dec!(3.0), dec!(0));
 
-        let tx4 = Tx::new(4, Withdrawal, 1, Some(dec!(1.5)));
+        let tx4 = Tx::new(tx_id(4), Withdrawal, 1, Some(dec!(1.5)));
         let res4 = manager.add_transaction(tx4);
         assert_eq!(res4, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(1.5), dec!(0));
+        assert_balance(&manager, 1, dec!(1.5), dec!(0));
 
-        let tx5 = Tx::new(5, Withdrawal, 2, Some(dec!(3.0)));
+        let tx5 = Tx::new(tx_id(5), Withdrawal, 2, Some(dec!(3.0)));
         let res5 = manager.add_transaction(tx5);
         assert_eq!(res5, Err(TxError::InsufficientFunds(2)));
         // balance remains unchanged
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(1.5), dec!(0));
+        assert_balance(&manager, 1, dec!(1.5), dec!(0));
     }
 
     #[test]
     pub fn test_single_client_deposit_dispute_resolve() {
         let mut manager = TransactionManager::new();
 
-        let tx1 = Tx::new(1, Deposit, 1, Some(dec!(10.0)));
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
         let res1 = manager.add_transaction(tx1);
         assert_eq!(res1, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(10.0), dec!(0));
+        assert_balance(&manager, 1, dec!(10.0), dec!(0));
 
         // Dispute + Resolve
 
-        let tx2 = Tx::new(1, Dispute, 1, None);
+        let tx2 = Tx::new(tx_id(1), Dispute, 1, None);
         let res2 = manager.add_transaction(tx2);
         assert_eq!(res2, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(0), dec!(10.0));
+        assert_balance(&manager, 1, dec!(0), dec!(10.0));
 
-        let tx3 = Tx::new(1, Resolve, 1, None);
+        let tx3 = Tx::new(tx_id(1), Resolve, 1, None);
         let res3 = manager.add_transaction(tx3);
         assert_eq!(res3, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(10.0), dec!(0));
+        assert_balance(&manager, 1, dec!(10.0), dec!(0));
 
         // Dispute + Chargeback
 
-        let tx4 = Tx::new(1, Dispute, 1, None);
+        let tx4 = Tx::new(tx_id(1), Dispute, 1, None);
         let res4 = manager.add_transaction(tx4);
         assert_eq!(res4, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(0), dec!(10.0));
+        assert_balance(&manager, 1, dec!(0), dec!(10.0));
 
-        let tx5 = Tx::new(1, TransactionType::Chargeback, 1, None);
+        let tx5 = Tx::new(tx_id(1), TransactionType::Chargeback, 1, None);
         let res5 = manager.add_transaction(tx5);
         assert_eq!(res5, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(0), dec!(0));
+        assert_balance(&manager, 1, dec!(0), dec!(0));
     }
 
     #[test]
     pub fn test_single_client_withdrawal_dispute_resolve_chargeback() {
         let mut manager = TransactionManager::new();
 
-        let tx1 = Tx::new(1, Deposit, 1, Some(dec!(10.0)));
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
         let res1 = manager.add_transaction(tx1);
         assert_eq!(res1, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(10.0), dec!(0));
+        assert_balance(&manager, 1, dec!(10.0), dec!(0));
 
-        let tx2 = Tx::new(2, Withdrawal, 1, Some(dec!(5.0)));
+        let tx2 = Tx::new(tx_id(2), Withdrawal, 1, Some(dec!(5.0)));
         let res2 = manager.add_transaction(tx2);
         assert_eq!(res2, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(5.0), dec!(0));
+        assert_balance(&manager, 1, dec!(5.0), dec!(0));
 
         // Dispute + Resolve
 
-        let tx3 = Tx::new(2, Dispute, 1, None);
+        let tx3 = Tx::new(tx_id(2), Dispute, 1, None);
         let res3 = manager.add_transaction(tx3);
         assert_eq!(res3, Ok(()));
         // money is still available, even though it's held
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(5.0), dec!(5.0));
+        assert_balance(&manager, 1, dec!(5.0), dec!(5.0));
 
-        let tx4 = Tx::new(2, Resolve, 1, None);
+        let tx4 = Tx::new(tx_id(2), Resolve, 1, None);
         let res4 = manager.add_transaction(tx4);
         assert_eq!(res4, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(5.0), dec!(0));
+        assert_balance(&manager, 1, dec!(5.0), dec!(0));
 
         // Dispute + Chargeback
 
-        let tx5 = Tx::new(2, Dispute, 1, None);
+        let tx5 = Tx::new(tx_id(2), Dispute, 1, None);
         let res5 = manager.add_transaction(tx5);
         assert_eq!(res5, Ok(()));
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(5.0), dec!(5.0));
+        assert_balance(&manager, 1, dec!(5.0), dec!(5.0));
 
-        let tx6 = Tx::new(2, TransactionType::Chargeback, 1, None);
+        let tx6 = Tx::new(tx_id(2), TransactionType::Chargeback, 1, None);
         let res6 = manager.add_transaction(tx6);
         assert_eq!(res6, Ok(()));
         // withdrawn money is returned
-        assert_balance(manager.client_db.get(&1).unwrap(), dec!(10.0), dec!(0));
+        assert_balance(&manager, 1, dec!(10.0), dec!(0));
+    }
+
+    #[test]
+    pub fn test_dispute_window() {
+        let day = 24 * 60 * 60;
+        let mut manager = TransactionManager::new().with_dispute_window(120 * day);
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0))).with_timestamp(0);
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        let tx2 = Tx::new(tx_id(2), Deposit, 1, Some(dec!(5.0))).with_timestamp(100 * day);
+        assert_eq!(manager.add_transaction(tx2), Ok(()));
+
+        // tx 1 happened 150 days ago
+        let tx3 = Tx::new(tx_id(1), Dispute, 1, None).with_timestamp(150 * day);
+        let res3 = manager.add_transaction(tx3);
+        assert_eq!(res3, Err(TxError::DisputeWindowExpired(tx_id(1))));
+        assert_balance(&manager, 1, dec!(15.0), dec!(0));
+
+        // tx 2 happened 50 days ago
+        let tx4 = Tx::new(tx_id(2), Dispute, 1, None).with_timestamp(150 * day);
+        let res4 = manager.add_transaction(tx4);
+        assert_eq!(res4, Ok(()));
+        assert_balance(&manager, 1, dec!(10.0), dec!(5.0));
+
+        // without a timestamp the age of the dispute is unknown
+        let tx5 = Tx::new(tx_id(1), Dispute, 1, None);
+        let res5 = manager.add_transaction(tx5);
+        assert_eq!(res5, Ok(()));
+        assert_balance(&manager, 1, dec!(0), dec!(15.0));
+    }
+
+    #[test]
+    pub fn test_global_tx_ids() {
+        let mut manager = TransactionManager::new();
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+
+        // the same tx id for another client is a duplicate
+        let tx2 = Tx::new(tx_id(1), Deposit, 2, Some(dec!(5.0)));
+        let res2 = manager.add_transaction(tx2);
+        assert_eq!(res2, Err(TxError::TransactionAlreadyHappened(tx_id(1))));
+        assert_balance(&manager, 2, dec!(0), dec!(0));
+
+        // disputing someone else's transaction
+        let tx3 = Tx::new(tx_id(1), Dispute, 2, None);
+        let res3 = manager.add_transaction(tx3);
+        assert_eq!(res3, Err(TxError::ClientMismatch(tx_id(1), 2)));
+        assert_balance(&manager, 1, dec!(10.0), dec!(0));
+
+        let tx4 = Tx::new(tx_id(1), Dispute, 1, None);
+        assert_eq!(manager.add_transaction(tx4), Ok(()));
+
+        let tx5 = Tx::new(tx_id(1), Resolve, 2, None);
+        let res5 = manager.add_transaction(tx5);
+        assert_eq!(res5, Err(TxError::ClientMismatch(tx_id(1), 2)));
+        assert_balance(&manager, 1, dec!(0), dec!(10.0));
+    }
+
+    #[test]
+    pub fn test_per_client_tx_ids() {
+        let mut manager = TransactionManager::new().with_global_tx_ids(false);
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        let tx2 = Tx::new(tx_id(1), Deposit, 2, Some(dec!(5.0)));
+        assert_eq!(manager.add_transaction(tx2), Ok(()));
+
+        let tx3 = Tx::new(tx_id(1), Dispute, 2, None);
+        assert_eq!(manager.add_transaction(tx3), Ok(()));
+        assert_balance(&manager, 1, dec!(10.0), dec!(0));
+        assert_balance(&manager, 2, dec!(0), dec!(5.0));
+    }
+
+    #[test]
+    pub fn test_repeated_dispute_is_rejected() {
+        let mut manager = TransactionManager::new();
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        let tx2 = Tx::new(tx_id(1), Dispute, 1, None);
+        assert_eq!(manager.add_transaction(tx2.clone()), Ok(()));
+
+        let res3 = manager.add_transaction(tx2);
+        assert_eq!(res3, Err(TxError::TransactionAlreadyDisputed(tx_id(1))));
+        assert_balance(&manager, 1, dec!(0), dec!(10.0));
+        assert_eq!(manager.audit(), Ok(vec![]));
+    }
+
+    #[test]
+    pub fn test_resolved_transaction_can_be_disputed_again() {
+        let mut manager = TransactionManager::new();
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        let tx2 = Tx::new(tx_id(1), Dispute, 1, None);
+        assert_eq!(manager.add_transaction(tx2.clone()), Ok(()));
+        let tx3 = Tx::new(tx_id(1), Resolve, 1, None);
+        assert_eq!(manager.add_transaction(tx3), Ok(()));
+
+        let res4 = manager.add_transaction(tx2);
+        assert_eq!(res4, Ok(()));
+        assert_balance(&manager, 1, dec!(0), dec!(10.0));
+        assert_eq!(manager.audit(), Ok(vec![]));
+    }
+
+    #[test]
+    pub fn test_overflow_is_rejected() {
+        let mut manager = TransactionManager::new();
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(Decimal::MAX));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        let tx2 = Tx::new(tx_id(2), Deposit, 1, Some(dec!(1)));
+        assert_eq!(manager.add_transaction(tx2), Err(TxError::Overflow(1)));
+        assert_balance(&manager, 1, Decimal::MAX, dec!(0));
+
+        // the rejected deposit was not recorded, so it cannot be disputed
+        let tx3 = Tx::new(tx_id(2), Dispute, 1, None);
+        assert_eq!(
+            manager.add_transaction(tx3),
+            Err(TxError::TransactionNotFound(tx_id(2)))
+        );
+    }
+
+    #[test]
+    pub fn test_journal_overflow_is_rejected() {
+        let mut manager = TransactionManager::new().with_journal().with_event_log();
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(Decimal::MAX));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        // the settlement account overflows although client 2 could hold the amount
+        let tx2 = Tx::new(tx_id(2), Deposit, 2, Some(Decimal::MAX));
+        assert_eq!(manager.add_transaction(tx2), Err(TxError::Overflow(2)));
+        assert_balance(&manager, 2, dec!(0), dec!(0));
+
+        // nothing of the rejected deposit was kept, so it cannot be disputed
+        let tx3 = Tx::new(tx_id(2), Dispute, 2, None);
+        assert_eq!(
+            manager.add_transaction(tx3),
+            Err(TxError::TransactionNotFound(tx_id(2)))
+        );
+        let offsets: Vec<u64> = manager
+            .take_events()
+            .iter()
+            .map(|event| event.get_offset())
+            .collect();
+        assert_eq!(offsets, vec![0, 1, 2]);
+        let journal = manager.take_journal().unwrap();
+        let clients = manager.get_all_values().unwrap();
+        assert_eq!(journal.trial_balance().is_balanced(), Ok(true));
+        assert_eq!(journal.reconcile(&clients), vec![]);
+    }
+
+    #[test]
+    pub fn test_metrics() {
+        let mut manager = TransactionManager::new().with_metrics();
+
+        let tx1 = Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)));
+        assert_eq!(manager.add_transaction(tx1), Ok(()));
+        let tx2 = Tx::new(tx_id(1), Dispute, 1, None);
+        assert_eq!(manager.add_transaction(tx2.clone()), Ok(()));
+        assert!(manager.add_transaction(tx2).is_err());
+
+        let metrics = manager.take_metrics().unwrap();
+        assert_eq!(metrics.get_transactions(&Deposit, true), 1);
+        assert_eq!(metrics.get_transactions(&Dispute, true), 1);
+        assert_eq!(metrics.get_transactions(&Dispute, false), 1);
+        assert_eq!(metrics.get_rejections("transaction_already_disputed"), 1);
+        assert_eq!(manager.count_open_disputes(), Ok(1));
     }
 
-    fn assert_balance(client: &Client, available: Decimal, held: Decimal) {
+    fn assert_balance(
+        manager: &TransactionManager,
+        client_id: ClientId,
+        available: Decimal,
+        held: Decimal,
+    ) {
+        let client = manager.client_db.get(client_id).unwrap().unwrap();
         assert_eq!(client.get_available(), available);
         assert_eq!(client.get_held(), held);
     }
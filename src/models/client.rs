@@ -4,6 +4,7 @@ use crate::managers::transaction_manager::TxError;
 use crate::models::client_snapshot::ClientSnapshot;
 use rust_decimal::Decimal;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Client {
     client_id: ClientId,
     available: Decimal,
@@ -21,17 +22,31 @@ impl Client {
         }
     }
 
+    /// Rebuilds a client from previously stored balances.
+    pub fn restore(client_id: ClientId, available: Decimal, held: Decimal, locked: bool) -> Self {
+        Client {
+            client_id,
+            available,
+            held,
+            locked,
+        }
+    }
+
+    pub fn get_id(&self) -> ClientId {
+        self.client_id
+    }
+
     pub fn get_available(&self) -> Decimal {
         self.available
     }
 
-    #[cfg(test)]
     pub fn get_held(&self) -> Decimal {
         self.held
     }
 
-    pub fn deposit(&mut self, amount: Decimal) {
-        self.available += amount;
+    pub fn deposit(&mut self, amount: Decimal) -> Result<(), TxError> {
+        let available = self.add(self.available, amount)?;
+        self.set_balances(available, self.held)
     }
 
     pub fn withdraw(&mut self, amount: Decimal) -> Result<(), TxError> {
@@ -39,51 +54,76 @@ impl Client {
             return Err(TxError::InsufficientFunds(self.client_id));
         }
 
-        self.available -= amount;
-
-        Ok(())
+        let available = self.sub(self.available, amount)?;
+        self.set_balances(available, self.held)
     }
 
-    pub fn dispute(&mut self, amount: Decimal, tx_type: TransactionType) {
+    pub fn dispute(&mut self, amount: Decimal, tx_type: TransactionType) -> Result<(), TxError> {
         match tx_type {
             TransactionType::Deposit => {
-                self.available -= amount;
-                self.held += amount;
+                let available = self.sub(self.available, amount)?;
+                let held = self.add(self.held, amount)?;
+                self.set_balances(available, held)
             }
             TransactionType::Withdrawal => {
-                self.held += amount;
+                let held = self.add(self.held, amount)?;
+                self.set_balances(self.available, held)
             }
-            _ => {}
+            _ => Ok(()),
         }
     }
 
-    pub fn resolve(&mut self, amount: Decimal, tx_type: TransactionType) {
+    pub fn resolve(&mut self, amount: Decimal, tx_type: TransactionType) -> Result<(), TxError> {
         match tx_type {
             TransactionType::Deposit => {
-                self.available += amount; // resolve the transaction (cancel dispute)
-                self.held -= amount; // release held amount
+                let available = self.add(self.available, amount)?; // resolve the transaction (cancel dispute)
+                let held = self.sub(self.held, amount)?; // release held amount
+                self.set_balances(available, held)
             }
             TransactionType::Withdrawal => {
-                self.held -= amount; // release held amount
+                let held = self.sub(self.held, amount)?; // release held amount
+                self.set_balances(self.available, held)
             }
-            _ => {}
+            _ => Ok(()),
         }
     }
 
-    pub fn chargeback(&mut self, amount: Decimal, tx_type: TransactionType) {
+    pub fn chargeback(&mut self, amount: Decimal, tx_type: TransactionType) -> Result<(), TxError> {
         match tx_type {
             TransactionType::Deposit => {
                 // just release held amount. The amount is already deducted from available
-                self.held -= amount;
+                let held = self.sub(self.held, amount)?;
+                self.set_balances(self.available, held)
             }
             TransactionType::Withdrawal => {
-                self.held -= amount;
-                self.available += amount;
+                let held = self.sub(self.held, amount)?;
+                let available = self.add(self.available, amount)?;
+                self.set_balances(available, held)
             }
-            _ => {}
+            _ => Ok(()),
         }
     }
 
+    fn add(&self, balance: Decimal, amount: Decimal) -> Result<Decimal, TxError> {
+        balance
+            .checked_add(amount)
+            .ok_or(TxError::Overflow(self.client_id))
+    }
+
+    fn sub(&self, balance: Decimal, amount: Decimal) -> Result<Decimal, TxError> {
+        balance
+            .checked_sub(amount)
+            .ok_or(TxError::Overflow(self.client_id))
+    }
+
+    // the balances only change if the total still fits, so a snapshot can always be taken
+    fn set_balances(&mut self, available: Decimal, held: Decimal) -> Result<(), TxError> {
+        self.add(available, held)?;
+        self.available = available;
+        self.held = held;
+        Ok(())
+    }
+
     pub fn freeze(&mut self) {
         self.locked = true;
     }
@@ -120,16 +160,20 @@ mod tests {
         let client_id = 1;
         let mut client = Client::new(client_id);
 
-        client.deposit(Decimal::new(200, 2));
+        client.deposit(Decimal::new(200, 2)).unwrap();
         assert_eq!(client.available, Decimal::new(200, 2));
         assert_eq!(client.held, Decimal::ZERO);
 
-        client.dispute(Decimal::new(200, 2), TransactionType::Deposit);
+        client
+            .dispute(Decimal::new(200, 2), TransactionType::Deposit)
+            .unwrap();
         assert_eq!(client.available, Decimal::ZERO);
         assert_eq!(client.held, Decimal::new(200, 2));
 
         // Act & Assert
-        client.resolve(Decimal::new(200, 2), TransactionType::Deposit);
+        client
+            .resolve(Decimal::new(200, 2), TransactionType::Deposit)
+            .unwrap();
         assert_eq!(client.available, Decimal::new(200, 2));
         assert_eq!(client.held, Decimal::ZERO);
     }
@@ -139,16 +183,20 @@ mod tests {
         let client_id = 1;
         let mut client = Client::new(client_id);
 
-        client.deposit(Decimal::new(200, 2));
+        client.deposit(Decimal::new(200, 2)).unwrap();
         assert_eq!(client.available, Decimal::new(200, 2));
         assert_eq!(client.held, Decimal::ZERO);
 
-        client.dispute(Decimal::new(200, 2), TransactionType::Deposit);
+        client
+            .dispute(Decimal::new(200, 2), TransactionType::Deposit)
+            .unwrap();
         assert_eq!(client.available, Decimal::ZERO);
         assert_eq!(client.held, Decimal::new(200, 2));
 
         // Act & Assert
-        client.chargeback(Decimal::new(200, 2), TransactionType::Deposit);
+        client
+            .chargeback(Decimal::new(200, 2), TransactionType::Deposit)
+            .unwrap();
         assert_eq!(client.available, Decimal::ZERO);
         assert_eq!(client.held, Decimal::ZERO);
     }
@@ -163,18 +211,22 @@ mod tests {
         let client_id = 1;
         let mut client = Client::new(client_id);
 
-        client.deposit(Decimal::new(200, 2)); // to avoid negative balance
+        client.deposit(Decimal::new(200, 2)).unwrap(); // to avoid negative balance
         let wres = client.withdraw(Decimal::new(50, 2));
         assert_eq!(wres, Ok(()));
         assert_eq!(client.available, Decimal::new(150, 2));
         assert_eq!(client.held, Decimal::ZERO);
 
-        client.dispute(Decimal::new(50, 2), TransactionType::Withdrawal);
+        client
+            .dispute(Decimal::new(50, 2), TransactionType::Withdrawal)
+            .unwrap();
         assert_eq!(client.available, Decimal::new(150, 2));
         assert_eq!(client.held, Decimal::new(50, 2));
 
         // Act &  Assert
-        client.resolve(Decimal::new(50, 2), TransactionType::Withdrawal);
+        client
+            .resolve(Decimal::new(50, 2), TransactionType::Withdrawal)
+            .unwrap();
         assert_eq!(client.available, Decimal::new(150, 2));
         assert_eq!(client.held, Decimal::ZERO);
     }
@@ -185,18 +237,22 @@ mod tests {
         let client_id = 1;
         let mut client = Client::new(client_id);
 
-        client.deposit(Decimal::new(200, 2));
+        client.deposit(Decimal::new(200, 2)).unwrap();
         let wres = client.withdraw(Decimal::new(50, 2));
         assert_eq!(wres, Ok(()));
         assert_eq!(client.available, Decimal::new(150, 2));
         assert_eq!(client.held, Decimal::ZERO);
 
-        client.dispute(Decimal::new(50, 2), TransactionType::Withdrawal);
+        client
+            .dispute(Decimal::new(50, 2), TransactionType::Withdrawal)
+            .unwrap();
         assert_eq!(client.available, Decimal::new(150, 2));
         assert_eq!(client.held, Decimal::new(50, 2));
 
         // Act & Assert
-        client.chargeback(Decimal::new(50, 2), TransactionType::Withdrawal);
+        client
+            .chargeback(Decimal::new(50, 2), TransactionType::Withdrawal)
+            .unwrap();
         assert_eq!(client.available, Decimal::new(200, 2));
         assert_eq!(client.held, Decimal::ZERO);
     }
@@ -209,4 +265,39 @@ mod tests {
         let wres = client.withdraw(Decimal::new(50, 2));
         assert_eq!(wres, Err(TxError::InsufficientFunds(client_id)));
     }
+
+    /*
+       Overflow tests
+    */
+
+    #[test]
+    fn test_deposit_up_to_the_maximum() {
+        let mut client = Client::new(1);
+
+        assert_eq!(client.deposit(Decimal::MAX), Ok(()));
+        assert_eq!(client.deposit(Decimal::ONE), Err(TxError::Overflow(1)));
+        assert_eq!(client.available, Decimal::MAX);
+    }
+
+    #[test]
+    fn test_dispute_below_the_minimum() {
+        let mut client = Client::restore(1, Decimal::MIN, Decimal::ZERO, false);
+
+        let result = client.dispute(Decimal::ONE, TransactionType::Deposit);
+
+        assert_eq!(result, Err(TxError::Overflow(1)));
+        assert_eq!(client.available, Decimal::MIN);
+        assert_eq!(client.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_total_must_fit() {
+        let mut client = Client::restore(1, Decimal::MAX, Decimal::ZERO, false);
+
+        let result = client.dispute(Decimal::ONE, TransactionType::Withdrawal);
+
+        assert_eq!(result, Err(TxError::Overflow(1)));
+        assert_eq!(client.held, Decimal::ZERO);
+        assert_eq!(client.get_snapshot().get_total(), Decimal::MAX);
+    }
 }
@@ -4,7 +4,7 @@ use rust_decimal::Decimal;
 /**
  * ClientSnapshot is a snapshot of a client's account at a point in time.
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClientSnapshot {
     id: ClientId,
     available: Decimal,
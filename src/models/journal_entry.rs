@@ -0,0 +1,116 @@
+use crate::common::types::{ClientId, TransactionId};
+use rust_decimal::Decimal;
+use std::fmt;
+
+/// An account of the double-entry ledger. Client balances are credit balances.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LedgerAccount {
+    ClientAvailable(ClientId),
+    ClientHeld(ClientId),
+    ExternalSettlement,
+    /// The engine does not charge fees yet, so nothing is posted here.
+    Fees,
+    ChargebackLoss,
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LedgerAccount::ClientAvailable(client_id) => {
+                write!(f, "client:{}:available", client_id)
+            }
+            LedgerAccount::ClientHeld(client_id) => write!(f, "client:{}:held", client_id),
+            LedgerAccount::ExternalSettlement => f.write_str("external_settlement"),
+            LedgerAccount::Fees => f.write_str("fees"),
+            LedgerAccount::ChargebackLoss => f.write_str("chargeback_loss"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    account: LedgerAccount,
+    side: Side,
+    amount: Decimal,
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount: Decimal) -> Self {
+        Posting {
+            account,
+            side: Side::Debit,
+            amount,
+        }
+    }
+
+    pub fn credit(account: LedgerAccount, amount: Decimal) -> Self {
+        Posting {
+            account,
+            side: Side::Credit,
+            amount,
+        }
+    }
+
+    pub fn get_account(&self) -> &LedgerAccount {
+        &self.account
+    }
+
+    pub fn get_side(&self) -> Side {
+        self.side
+    }
+
+    pub fn get_amount(&self) -> Decimal {
+        self.amount
+    }
+}
+
+/**
+ * JournalEntry is the set of postings made for a single ledger event.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    event_offset: u64,
+    tx_id: TransactionId,
+    postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    pub fn new(event_offset: u64, tx_id: TransactionId, postings: Vec<Posting>) -> Self {
+        JournalEntry {
+            event_offset,
+            tx_id,
+            postings,
+        }
+    }
+
+    pub fn get_event_offset(&self) -> u64 {
+        self.event_offset
+    }
+
+    pub fn get_transaction_id(&self) -> TransactionId {
+        self.tx_id
+    }
+
+    pub fn get_postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        let mut debits = Decimal::ZERO;
+        let mut credits = Decimal::ZERO;
+        for posting in self.postings.iter() {
+            match posting.side {
+                Side::Debit => debits += posting.amount,
+                Side::Credit => credits += posting.amount,
+            }
+        }
+
+        debits == credits
+    }
+}
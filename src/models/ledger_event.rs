@@ -0,0 +1,111 @@
+use crate::common::types::{ClientId, TransactionId, TransactionType};
+use rust_decimal::Decimal;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerEventKind {
+    /// The client's first transaction was seen, even if it was rejected.
+    AccountOpened,
+    Deposited {
+        amount: Decimal,
+    },
+    Withdrawn {
+        amount: Decimal,
+    },
+    FundsHeld {
+        amount: Decimal,
+        disputed_type: TransactionType,
+    },
+    FundsReleased {
+        amount: Decimal,
+        disputed_type: TransactionType,
+    },
+    ChargedBack {
+        amount: Decimal,
+        disputed_type: TransactionType,
+    },
+    AccountLocked,
+}
+
+/**
+ * LedgerEvent records a single change to a client's account.
+ * `offset` is the index of the event in the log and `position` is the number of
+ * transactions that had been offered to the transaction manager when it happened.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerEvent {
+    offset: u64,
+    position: u64,
+    client_id: ClientId,
+    tx_id: TransactionId,
+    kind: LedgerEventKind,
+}
+
+impl LedgerEvent {
+    pub fn new(
+        offset: u64,
+        position: u64,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        kind: LedgerEventKind,
+    ) -> Self {
+        LedgerEvent {
+            offset,
+            position,
+            client_id,
+            tx_id,
+            kind,
+        }
+    }
+
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn get_client_id(&self) -> ClientId {
+        self.client_id
+    }
+
+    pub fn get_transaction_id(&self) -> TransactionId {
+        self.tx_id
+    }
+
+    pub fn get_kind(&self) -> &LedgerEventKind {
+        &self.kind
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self.kind {
+            LedgerEventKind::AccountOpened => "account_opened",
+            LedgerEventKind::Deposited { .. } => "deposited",
+            LedgerEventKind::Withdrawn { .. } => "withdrawn",
+            LedgerEventKind::FundsHeld { .. } => "funds_held",
+            LedgerEventKind::FundsReleased { .. } => "funds_released",
+            LedgerEventKind::ChargedBack { .. } => "charged_back",
+            LedgerEventKind::AccountLocked => "account_locked",
+        }
+    }
+
+    pub fn get_amount(&self) -> Option<Decimal> {
+        match &self.kind {
+            LedgerEventKind::Deposited { amount }
+            | LedgerEventKind::Withdrawn { amount }
+            | LedgerEventKind::FundsHeld { amount, .. }
+            | LedgerEventKind::FundsReleased { amount, .. }
+            | LedgerEventKind::ChargedBack { amount, .. } => Some(*amount),
+            LedgerEventKind::AccountOpened | LedgerEventKind::AccountLocked => None,
+        }
+    }
+
+    pub fn get_disputed_type(&self) -> Option<TransactionType> {
+        match &self.kind {
+            LedgerEventKind::FundsHeld { disputed_type, .. }
+            | LedgerEventKind::FundsReleased { disputed_type, .. }
+            | LedgerEventKind::ChargedBack { disputed_type, .. } => Some(disputed_type.clone()),
+            _ => None,
+        }
+    }
+}
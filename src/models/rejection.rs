@@ -0,0 +1,100 @@
+use crate::managers::transaction_manager::TxError;
+use crate::models::transaction::Transaction;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/**
+ * Location is where a row came from: its line and, when several files are read, the file.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    file: Option<PathBuf>,
+    line: u64,
+}
+
+impl Location {
+    pub fn new(file: Option<PathBuf>, line: u64) -> Self {
+        Location { file, line }
+    }
+
+    pub fn get_file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// 1-based within the file, the header being line 1. 0 if the reader could not tell.
+    pub fn get_line(&self) -> u64 {
+        self.line
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{} line {}", file.display(), self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
+/**
+ * Rejection is a transaction the ledger refused, together with where it came from.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    location: Location,
+    transaction: Transaction,
+    error: TxError,
+}
+
+impl Rejection {
+    pub fn new(location: Location, transaction: Transaction, error: TxError) -> Self {
+        Rejection {
+            location,
+            transaction,
+            error,
+        }
+    }
+
+    pub fn get_location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn get_line(&self) -> u64 {
+        self.location.get_line()
+    }
+
+    pub fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn get_error(&self) -> &TxError {
+        &self.error
+    }
+}
+
+/**
+ * InvalidRow is an input row that could not be read as a transaction.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidRow {
+    location: Location,
+    message: String,
+}
+
+impl InvalidRow {
+    pub fn new(location: Location, message: String) -> Self {
+        InvalidRow { location, message }
+    }
+
+    pub fn get_location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn get_line(&self) -> u64 {
+        self.location.get_line()
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
@@ -1,14 +1,21 @@
-use crate::common::types::{ClientId, TransactionId, TransactionType};
-use csv::StringRecord;
+use crate::common::types::{ClientId, Timestamp, TransactionId, TransactionType};
+use csv::{ByteRecord, StringRecord};
 use rust_decimal::Decimal;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Transaction {
     tx_id: TransactionId,
     tx_type: TransactionType,
     client_id: ClientId,
     amount: Option<Decimal>,
+    timestamp: Option<Timestamp>,
+    metadata: Vec<(String, String)>,
 }
 
 impl Transaction {
@@ -23,9 +30,21 @@ impl Transaction {
             tx_type,
             client_id,
             amount,
+            timestamp: None,
+            metadata: Vec::new(),
         }
     }
 
+    pub fn with_timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Vec<(String, String)>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     pub fn get_transaction_id(&self) -> TransactionId {
         self.tx_id
     }
@@ -41,12 +60,107 @@ impl Transaction {
     pub fn get_amount(&self) -> Option<Decimal> {
         self.amount
     }
+
+    pub fn get_timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// The input columns the engine does not know, as name and value pairs in input order.
+    /// They travel with the transaction but are not kept by the stores.
+    pub fn get_metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
 }
 
 const COL_TX_TYPE: usize = 0;
 const COL_CLIENT_ID: usize = 1;
 const COL_TX_ID: usize = 2;
 const COL_AMOUNT: usize = 3;
+const COL_TIMESTAMP: usize = 4;
+
+/**
+ * ColumnMap tells which column of a row holds which field. The default is the positional
+ * layout `type, client, tx, amount[, timestamp]`; `from_header` follows the names in a header.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMap {
+    tx_type: usize,
+    client_id: usize,
+    tx_id: usize,
+    amount: usize,
+    timestamp: Option<usize>,
+    // unknown columns by index and name, kept as metadata
+    extra: Vec<(usize, String)>,
+    // the header with the known columns under their canonical names, for deserializing a `Row`
+    header: ByteRecord,
+}
+
+impl Default for ColumnMap {
+    fn default() -> Self {
+        ColumnMap {
+            tx_type: COL_TX_TYPE,
+            client_id: COL_CLIENT_ID,
+            tx_id: COL_TX_ID,
+            amount: COL_AMOUNT,
+            timestamp: Some(COL_TIMESTAMP),
+            extra: Vec::new(),
+            header: ByteRecord::from(COLUMN_NAMES.to_vec()),
+        }
+    }
+}
+
+impl ColumnMap {
+    /// Maps the columns by name, in any order and regardless of case. `type`, `client`, `tx`
+    /// and `amount` are required, `timestamp` is optional and anything else is metadata.
+    pub fn from_header(header: &StringRecord) -> Result<Self, TxError> {
+        let mut known: [Option<usize>; 5] = [None; 5];
+        let mut extra = Vec::new();
+        let mut canonical = ByteRecord::new();
+
+        for (index, name) in header.iter().enumerate() {
+            let name = name.trim();
+            match column_position(name) {
+                Some(position) if known[position].is_some() => {
+                    return Err(TxError(format!(
+                        "Duplicate {} column",
+                        COLUMN_NAMES[position]
+                    )))
+                }
+                Some(position) => {
+                    known[position] = Some(index);
+                    canonical.push_field(COLUMN_NAMES[position].as_bytes());
+                }
+                None => {
+                    extra.push((index, name.to_string()));
+                    canonical.push_field(name.as_bytes());
+                }
+            }
+        }
+
+        let required = |position: usize| {
+            known[position]
+                .ok_or_else(|| TxError(format!("Missing {} column", COLUMN_NAMES[position])))
+        };
+        Ok(ColumnMap {
+            tx_type: required(COL_TX_TYPE)?,
+            client_id: required(COL_CLIENT_ID)?,
+            tx_id: required(COL_TX_ID)?,
+            amount: required(COL_AMOUNT)?,
+            timestamp: known[COL_TIMESTAMP],
+            extra,
+            header: canonical,
+        })
+    }
+}
+
+// the header names of the columns, in the positional order
+const COLUMN_NAMES: [&str; 5] = ["type", "client", "tx", "amount", "timestamp"];
+
+fn column_position(name: &str) -> Option<usize> {
+    COLUMN_NAMES
+        .iter()
+        .position(|known_name| known_name.eq_ignore_ascii_case(name))
+}
 
 #[derive(Debug)]
 pub struct TxError(String);
@@ -63,35 +177,147 @@ impl TryFrom<StringRecord> for Transaction {
     type Error = TxError;
 
     fn try_from(value: StringRecord) -> Result<Self, Self::Error> {
-        // there should be 4 columns in the input row
-        if value.len() != 4 {
+        // there should be 4 columns in the input row, plus an optional timestamp
+        if value.len() != 4 && value.len() != 5 {
             return Err(TxError(
-                "Invalid number of columns. Columns: type, client, tx, amount".into(),
+                "Invalid number of columns. Columns: type, client, tx, amount[, timestamp]".into(),
             ));
         }
 
-        let col_tx_id = value.get(COL_TX_ID).unwrap().trim();
-        let col_tx_type = value.get(COL_TX_TYPE).unwrap().trim();
-        let col_client_id = value.get(COL_CLIENT_ID).unwrap().trim();
-        let col_amount = value.get(COL_AMOUNT).unwrap().trim();
+        Transaction::from_record(&value, &ColumnMap::default())
+    }
+}
+
+/// One input row, borrowed from the reader's record buffer.
+#[derive(Deserialize)]
+struct Row<'a> {
+    #[serde(rename = "type")]
+    tx_type: &'a str,
+    client: &'a str,
+    tx: &'a str,
+    amount: &'a str,
+    #[serde(default)]
+    timestamp: &'a str,
+}
+
+impl Transaction {
+    /// Reads a row laid out as described by `columns`.
+    pub fn from_record(value: &StringRecord, columns: &ColumnMap) -> Result<Self, TxError> {
+        let row = Row {
+            tx_type: column(value, columns.tx_type, "type")?,
+            client: column(value, columns.client_id, "client id")?,
+            tx: column(value, columns.tx_id, "transaction id")?,
+            amount: column(value, columns.amount, "amount")?,
+            timestamp: columns
+                .timestamp
+                .and_then(|index| value.get(index))
+                .unwrap_or_default(),
+        };
+
+        let mut transaction = Transaction::from_row(&row)?;
+        transaction.metadata = metadata(columns, |index| value.get(index).map(Cow::Borrowed));
+        Ok(transaction)
+    }
+
+    /// Reads a row like `from_record`, but borrows the fields straight from the raw record
+    /// instead of validating and copying the whole row first.
+    pub fn from_byte_record(value: &ByteRecord, columns: &ColumnMap) -> Result<Self, TxError> {
+        // a positional row may leave out the optional timestamp
+        let header = match value.len() < columns.header.len() {
+            true => Cow::Owned(columns.header.iter().take(value.len()).collect()),
+            false => Cow::Borrowed(&columns.header),
+        };
+        let row: Row = value
+            .deserialize(Some(&header))
+            .map_err(|err| TxError(err.to_string()))?;
+
+        let mut transaction = Transaction::from_row(&row)?;
+        if !columns.extra.is_empty() {
+            transaction.metadata = metadata(columns, |index| {
+                value.get(index).map(String::from_utf8_lossy)
+            });
+        }
+        Ok(transaction)
+    }
+
+    /// Reads one JSON Lines object. Its keys are matched like the names in a CSV header and
+    /// their values may be strings or numbers. Keys the engine does not know become metadata in
+    /// input order, with any other value kept as its JSON text.
+    pub fn from_json(line: &str) -> Result<Self, TxError> {
+        let JsonFields(fields) =
+            serde_json::from_str(line).map_err(|err| TxError(err.to_string()))?;
+
+        let mut known: [Option<Cow<str>>; 5] = Default::default();
+        let mut metadata: Vec<(String, String)> = Vec::new();
+        for (key, value) in fields.iter() {
+            let key = key.trim();
+            match column_position(key) {
+                Some(position) if known[position].is_some() => {
+                    return Err(TxError(format!(
+                        "Duplicate {} field",
+                        COLUMN_NAMES[position]
+                    )))
+                }
+                Some(position) => {
+                    known[position] = Some(json_text(value).ok_or_else(|| {
+                        TxError(format!("Invalid {}: expected a string or a number", key))
+                    })?)
+                }
+                None if metadata.iter().any(|(name, _)| name == key) => {
+                    return Err(TxError(format!("Duplicate {} field", key)))
+                }
+                None => {
+                    let text = match json_text(value) {
+                        Some(text) => text.trim().to_string(),
+                        None => json_compact(value),
+                    };
+                    metadata.push((key.to_string(), text));
+                }
+            }
+        }
 
-        let tx_id = match col_tx_id.parse::<TransactionId>() {
-            Ok(tx) => tx,
-            Err(_) => return Err(TxError("Invalid transaction id".into())),
+        // only the amount of a dispute, resolve or chargeback may be left out
+        let required = |position: usize| {
+            known[position]
+                .as_deref()
+                .ok_or_else(|| TxError(format!("Missing {} field", COLUMN_NAMES[position])))
         };
+        let row = Row {
+            tx_type: required(COL_TX_TYPE)?,
+            client: required(COL_CLIENT_ID)?,
+            tx: required(COL_TX_ID)?,
+            amount: known[COL_AMOUNT].as_deref().unwrap_or_default(),
+            timestamp: known[COL_TIMESTAMP].as_deref().unwrap_or_default(),
+        };
+
+        let mut transaction = Transaction::from_row(&row)?;
+        transaction.metadata = metadata;
+        Ok(transaction)
+    }
+
+    fn from_row(row: &Row) -> Result<Self, TxError> {
+        let col_tx_id = row.tx.trim();
+        let col_tx_type = row.tx_type.trim();
+        let col_client_id = row.client.trim();
+        let col_amount = row.amount.trim();
+        let col_timestamp = row.timestamp.trim();
+
+        let tx_id: TransactionId = parse_id(col_tx_id, "transaction id")?;
 
-        let tx_type = match col_tx_type {
-            "deposit" => TransactionType::Deposit,
-            "withdrawal" => TransactionType::Withdrawal,
-            "dispute" => TransactionType::Dispute,
-            "resolve" => TransactionType::Resolve,
-            "chargeback" => TransactionType::Chargeback,
-            _ => return Err(TxError("Invalid transaction type".into())),
+        let tx_type = match TransactionType::from_str(col_tx_type) {
+            Ok(tx_type) => tx_type,
+            Err(_) => return Err(TxError("Invalid transaction type".into())),
         };
 
-        let client_id: ClientId = match col_client_id.parse() {
-            Ok(client) => client,
-            Err(_) => return Err(TxError("Invalid client id".into())),
+        let client_id: ClientId = parse_id(col_client_id, "client id")?;
+
+        let timestamp = if col_timestamp.is_empty() {
+            None
+        } else {
+            match col_timestamp.parse::<Timestamp>() {
+                Ok(timestamp) => Some(timestamp),
+                Err(_) => return Err(TxError("Invalid timestamp".into())),
+            }
         };
 
         // If one of these transaction types were specified, the amount should be empty.
@@ -104,7 +330,9 @@ impl TryFrom<StringRecord> for Transaction {
                 return Err(TxError("An amount should be empty".into()));
             }
 
-            return Ok(Transaction::new(tx_id, tx_type, client_id, None));
+            let mut transaction = Transaction::new(tx_id, tx_type, client_id, None);
+            transaction.timestamp = timestamp;
+            return Ok(transaction);
         }
 
         let amount = match Decimal::from_str(col_amount) {
@@ -112,13 +340,95 @@ impl TryFrom<StringRecord> for Transaction {
             Err(_) => return Err(TxError("Invalid amount".into())),
         };
 
-        Ok(Transaction::new(tx_id, tx_type, client_id, Some(amount)))
+        let mut transaction = Transaction::new(tx_id, tx_type, client_id, Some(amount));
+        transaction.timestamp = timestamp;
+        Ok(transaction)
+    }
+}
+
+fn metadata<'a, F>(columns: &ColumnMap, field: F) -> Vec<(String, String)>
+where
+    F: Fn(usize) -> Option<Cow<'a, str>>,
+{
+    columns
+        .extra
+        .iter()
+        .map(|(index, name)| {
+            let value = field(*index).unwrap_or_default();
+            (name.clone(), value.trim().to_string())
+        })
+        .collect()
+}
+
+// the text of a JSON value the way it would appear in a CSV field: strings unquoted, numbers
+// as written so that no precision is lost, and null as an empty field
+fn json_text(value: &RawValue) -> Option<Cow<'_, str>> {
+    let raw = value.get();
+    match raw.as_bytes().first() {
+        Some(b'"') => serde_json::from_str::<Cow<str>>(raw).ok(),
+        Some(b'-' | b'0'..=b'9') => Some(Cow::Borrowed(raw)),
+        _ if raw == "null" => Some(Cow::Borrowed("")),
+        _ => None,
+    }
+}
+
+// a bool, object or array value, without the whitespace of the input
+fn json_compact(value: &RawValue) -> String {
+    serde_json::from_str::<serde_json::Value>(value.get())
+        .map(|value| value.to_string())
+        .unwrap_or_else(|_| value.get().to_string())
+}
+
+// the fields of a JSON object in input order, repeated keys included, where a map would sort
+// them and keep only the last of a repeated key
+struct JsonFields(Vec<(String, Box<RawValue>)>);
+
+impl<'de> Deserialize<'de> for JsonFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = JsonFields;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonFields, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(JsonFields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+fn column<'a>(record: &'a StringRecord, index: usize, name: &str) -> Result<&'a str, TxError> {
+    match record.get(index) {
+        Some(value) => Ok(value.trim()),
+        None => Err(TxError(format!("Missing {} column", name))),
     }
 }
 
+// the parse error tells an id that is out of range for the configured width apart from garbage
+fn parse_id<T>(value: &str, name: &str) -> Result<T, TxError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|err| TxError(format!("Invalid {} '{}': {}", name, value, err)))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::types::{tx_id, OpaqueId};
     use csv::StringRecord;
     use rstest::rstest;
     use rust_decimal::Decimal;
@@ -126,23 +436,23 @@ mod test {
     #[rstest]
     #[case(
         vec!["deposit", "1", "1", "10.0"],
-        Transaction::new(1, TransactionType::Deposit, 1, Some(Decimal::new(100, 1)))
+        Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(Decimal::new(100, 1)))
     )]
     #[case(
         vec!["withdrawal", "1", "1", "10.0"],
-        Transaction::new(1, TransactionType::Withdrawal, 1, Some(Decimal::new(100, 1)))
+        Transaction::new(tx_id(1), TransactionType::Withdrawal, 1, Some(Decimal::new(100, 1)))
     )]
     #[case(
         vec!["dispute", "1", "1", ""],
-        Transaction::new(1, TransactionType::Dispute, 1, None)
+        Transaction::new(tx_id(1), TransactionType::Dispute, 1, None)
     )]
     #[case(
         vec!["resolve", "1", "1", ""],
-        Transaction::new(1, TransactionType::Resolve, 1, None)
+        Transaction::new(tx_id(1), TransactionType::Resolve, 1, None)
     )]
     #[case(
         vec!["chargeback", "1", "1", ""],
-        Transaction::new(1, TransactionType::Chargeback, 1, None)
+        Transaction::new(tx_id(1), TransactionType::Chargeback, 1, None)
     )]
     fn test_transaction_from_string_record(
         #[case] input_vec: Vec<&str>,
@@ -170,10 +480,50 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[rstest]
+    #[case(
+        vec!["deposit", "1", "1", "10.0", "1700000000"],
+        Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(Decimal::new(100, 1)))
+            .with_timestamp(1700000000)
+    )]
+    #[case(
+        vec!["dispute", "1", "1", "", "1700000000"],
+        Transaction::new(tx_id(1), TransactionType::Dispute, 1, None).with_timestamp(1700000000)
+    )]
+    #[case(
+        vec!["deposit", "1", "1", "10.0", ""],
+        Transaction::new(tx_id(1), TransactionType::Deposit, 1, Some(Decimal::new(100, 1)))
+    )]
+    fn test_transaction_with_timestamp_from_string_record(
+        #[case] input_vec: Vec<&str>,
+        #[case] expected: Transaction,
+    ) {
+        // Arrange
+        let record = StringRecord::from(input_vec);
+
+        // Act
+        let transaction = Transaction::try_from(record).unwrap();
+
+        // Assert
+        assert_eq!(transaction, expected);
+    }
+
+    #[test]
+    fn test_invalid_timestamp() {
+        // Arrange
+        let record = StringRecord::from(vec!["deposit", "1", "1", "10.0", "yesterday"]);
+
+        // Act
+        let result = Transaction::try_from(record);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_invalid_number_of_columns() {
         // Arrange
-        let record = StringRecord::from(vec!["deposit", "1", "1", "10.0", "extra"]);
+        let record = StringRecord::from(vec!["deposit", "1", "1", "10.0", "1", "extra"]);
 
         // Act
         let result = Transaction::try_from(record);
@@ -194,10 +544,26 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_client_id_out_of_range() {
+        // Arrange
+        let too_big = (ClientId::MAX as u128 + 1).to_string();
+        let record = StringRecord::from(vec!["deposit", &too_big, "1", "10.0"]);
+
+        // Act
+        let result = Transaction::try_from(record);
+
+        // Assert
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("too large"), "{}", err);
+    }
+
     #[test]
     fn test_invalid_transaction_id() {
         // Arrange
-        let record = StringRecord::from(vec!["deposit", "1", "invalid", "10.0"]);
+        // too long for a string id and not a number
+        let invalid = "x".repeat(OpaqueId::CAPACITY + 1);
+        let record = StringRecord::from(vec!["deposit", "1", &invalid, "10.0"]);
 
         // Act
         let result = Transaction::try_from(record);
@@ -229,4 +595,163 @@ mod test {
         // Assert
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_columns_from_header() {
+        // Arrange
+        let header = StringRecord::from(vec!["Amount", " TX", "source", "type", "client"]);
+        let record = StringRecord::from(vec!["10.0", "1", "bank", "Deposit", "2"]);
+
+        // Act
+        let columns = ColumnMap::from_header(&header).unwrap();
+        let transaction = Transaction::from_record(&record, &columns).unwrap();
+
+        // Assert
+        let expected = Transaction::new(
+            tx_id(1),
+            TransactionType::Deposit,
+            2,
+            Some(Decimal::new(100, 1)),
+        )
+        .with_metadata(vec![("source".to_string(), "bank".to_string())]);
+        assert_eq!(transaction, expected);
+    }
+
+    #[rstest]
+    #[case(vec!["type", "client", "amount"], "Missing tx column")]
+    #[case(vec!["type", "client", "tx", "amount", "Client"], "Duplicate client column")]
+    fn test_invalid_header(#[case] header: Vec<&str>, #[case] message: &str) {
+        // Arrange
+        let header = StringRecord::from(header);
+
+        // Act
+        let result = ColumnMap::from_header(&header);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("Transaction error: {}", message)
+        );
+    }
+
+    #[rstest]
+    #[case(vec!["deposit", " 1", " 1", " 10.0"])]
+    #[case(vec!["dispute", "1", "1", ""])]
+    #[case(vec!["deposit", "1", "1", "10.0", "1700000000"])]
+    #[case(vec!["refund", "1", "1", "10.0"])]
+    #[case(vec!["deposit", "1", "1"])]
+    fn test_byte_record_matches_string_record(#[case] input_vec: Vec<&str>) {
+        // Arrange
+        let columns = ColumnMap::default();
+        let string_record = StringRecord::from(input_vec.clone());
+        let byte_record = ByteRecord::from(input_vec);
+
+        // Act
+        let from_string = Transaction::from_record(&string_record, &columns);
+        let from_bytes = Transaction::from_byte_record(&byte_record, &columns);
+
+        // Assert
+        assert_eq!(from_bytes.ok(), from_string.ok());
+    }
+
+    #[test]
+    fn test_byte_record_with_header() {
+        // Arrange
+        let header = StringRecord::from(vec!["client", "note", "TYPE", "tx", "amount"]);
+        let columns = ColumnMap::from_header(&header).unwrap();
+        let record = ByteRecord::from(vec!["2", " late ", "Withdrawal", "7", "1.5"]);
+        let invalid = ByteRecord::from(vec![&b"2"[..], b"", b"deposit", b"\xff", b"1.5"]);
+
+        // Act
+        let transaction = Transaction::from_byte_record(&record, &columns).unwrap();
+        let result = Transaction::from_byte_record(&invalid, &columns);
+
+        // Assert
+        let expected = Transaction::new(
+            tx_id(7),
+            TransactionType::Withdrawal,
+            2,
+            Some(Decimal::new(15, 1)),
+        )
+        .with_metadata(vec![("note".to_string(), "late".to_string())]);
+        assert_eq!(transaction, expected);
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "10.0"}"#, vec!["deposit", "1", "1", "10.0"])]
+    #[case(r#"{"type": "deposit", "client": "1", "tx": 1, "amount": 10.12345}"#, vec!["deposit", "1", "1", "10.12345"])]
+    #[case(r#"{"type": "dispute", "client": 1, "tx": 1}"#, vec!["dispute", "1", "1", ""])]
+    #[case(r#"{"type": "dispute", "client": 1, "tx": 1, "amount": null}"#, vec!["dispute", "1", "1", ""])]
+    #[case(r#"{"tx": 1, "client": 1, "amount": 1, "Type": "Withdrawal", "timestamp": 1700000000}"#, vec!["withdrawal", "1", "1", "1", "1700000000"])]
+    fn test_json_matches_string_record(#[case] line: &str, #[case] input_vec: Vec<&str>) {
+        // Arrange
+        let record = StringRecord::from(input_vec);
+
+        // Act
+        let from_json = Transaction::from_json(line).unwrap();
+        let from_record = Transaction::try_from(record).unwrap();
+
+        // Assert
+        assert_eq!(from_json, from_record);
+    }
+
+    #[rstest]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": -}"#)]
+    #[case(r#"{"type": "deposit", "client": 1, "amount": "1.0"}"#)]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1}"#)]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": true}"#)]
+    #[case(r#"{"type": "dispute", "client": 1, "tx": 1, "amount": 1}"#)]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1, "TX": 2, "amount": 1}"#)]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1, "tx": 2, "amount": 1}"#)]
+    #[case(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 1, "note": "a", "note": "b"}"#)]
+    #[case(r#"["deposit", 1, 1, 1]"#)]
+    fn test_invalid_json(#[case] line: &str) {
+        // Act
+        let result = Transaction::from_json(line);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_json_metadata() {
+        // Arrange
+        let line = r#"{"type": "deposit", "client": 2, "tx": 7, "amount": 1.5, "channel": "web"}"#;
+
+        // Act
+        let transaction = Transaction::from_json(line).unwrap();
+
+        // Assert
+        let expected = Transaction::new(
+            tx_id(7),
+            TransactionType::Deposit,
+            2,
+            Some(Decimal::new(15, 1)),
+        )
+        .with_metadata(vec![("channel".to_string(), "web".to_string())]);
+        assert_eq!(transaction, expected);
+    }
+
+    #[test]
+    fn test_json_metadata_keeps_input_order() {
+        // Arrange
+        let line = r#"{"type": "deposit", "zone": "eu", "client": 2, "tx": 7, "amount": 1,
+            "agent": {"id": 4, "tags": ["a", "b"]}, "flagged": true, "batch": 12}"#;
+
+        // Act
+        let transaction = Transaction::from_json(line).unwrap();
+
+        // Assert
+        let expected = [
+            ("zone".to_string(), "eu".to_string()),
+            (
+                "agent".to_string(),
+                r#"{"id":4,"tags":["a","b"]}"#.to_string(),
+            ),
+            ("flagged".to_string(), "true".to_string()),
+            ("batch".to_string(), "12".to_string()),
+        ];
+        assert_eq!(transaction.get_metadata(), &expected);
+    }
 }
@@ -0,0 +1,46 @@
+use crate::common::types::ClientId;
+use crate::models::client::Client;
+use crate::stores::store_error::StoreError;
+use std::collections::HashMap;
+
+pub type AccountStoreIter<'a> = Box<dyn Iterator<Item = Result<Client, StoreError>> + 'a>;
+
+/**
+ * AccountStore keeps the current state of every client account.
+ */
+pub trait AccountStore {
+    fn get(&self, client_id: ClientId) -> Result<Option<Client>, StoreError>;
+    fn upsert(&mut self, client: Client) -> Result<(), StoreError>;
+    fn iter(&self) -> Result<AccountStoreIter<'_>, StoreError>;
+
+    /// Makes the writes so far permanent, once a run has gone through all of its rows.
+    fn commit(&mut self) -> Result<(), StoreError> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryAccountStore {
+    clients: HashMap<ClientId, Client>,
+}
+
+impl MemoryAccountStore {
+    pub fn new() -> Self {
+        MemoryAccountStore::default()
+    }
+}
+
+impl AccountStore for MemoryAccountStore {
+    fn get(&self, client_id: ClientId) -> Result<Option<Client>, StoreError> {
+        Ok(self.clients.get(&client_id).cloned())
+    }
+
+    fn upsert(&mut self, client: Client) -> Result<(), StoreError> {
+        self.clients.insert(client.get_id(), client);
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<AccountStoreIter<'_>, StoreError> {
+        Ok(Box::new(self.clients.values().cloned().map(Ok)))
+    }
+}
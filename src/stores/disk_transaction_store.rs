@@ -0,0 +1,442 @@
+use crate::common::codec::Codec;
+use crate::stores::store_error::StoreError;
+use crate::stores::transaction_store::{
+    TransactionRecord, TransactionStore, TransactionStoreIter, TxKey,
+};
+use lru::LruCache;
+use redb::{
+    Database, Durability, ReadableTable, ReadableTableMetadata, TableDefinition, WriteTransaction,
+};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::ops::Bound;
+use std::path::Path;
+use std::vec::IntoIter;
+use tempfile::NamedTempFile;
+
+const HISTORY_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("tx_history");
+const META_TABLE: TableDefinition<&str, u32> = TableDefinition::new("meta");
+
+// format of the stored records; files from before it was recorded have no meta table and
+// a disputed flag that reads back as the undisputed or disputed state
+const SCHEMA_VERSION: u32 = 1;
+
+// inserts are written to the database in batches of this size
+const WRITE_BATCH_SIZE: usize = 50_000;
+// iterating reads the entries in pages of this size
+const PAGE_SIZE: usize = 1_000;
+// memory used by the database's own page cache
+const DATABASE_CACHE_BYTES: usize = 16 * 1024 * 1024;
+
+pub const DEFAULT_CACHE_ENTRIES: usize = 100_000;
+
+/**
+ * DiskTransactionStore keeps the transaction history in an embedded database file.
+ * Recently written or looked up transactions stay in a bounded in-memory cache. The writes
+ * are kept in one database transaction until `commit`, so dropping the store without it
+ * leaves the file as it was.
+ */
+pub struct DiskTransactionStore {
+    // reads go through it too, so they see the writes that are not committed yet
+    write_txn: Option<WriteTransaction>,
+    db: Database,
+    pending: HashMap<TxKey, TransactionRecord>,
+    cache: LruCache<TxKey, TransactionRecord>,
+    // removes the file on drop if no path was given
+    _temp_file: Option<NamedTempFile>,
+}
+
+impl DiskTransactionStore {
+    pub fn open<P: AsRef<Path>>(path: P, cache_entries: usize) -> Result<Self, StoreError> {
+        let db = Database::builder()
+            .set_cache_size(DATABASE_CACHE_BYTES)
+            .create(path)
+            .map_err(redb::Error::from)?;
+        DiskTransactionStore::from_database(db, cache_entries, None)
+    }
+
+    pub fn temporary(cache_entries: usize) -> Result<Self, StoreError> {
+        let temp_file = NamedTempFile::new()?;
+        let db = Database::builder()
+            .set_cache_size(DATABASE_CACHE_BYTES)
+            .create_file(temp_file.reopen()?)
+            .map_err(redb::Error::from)?;
+        DiskTransactionStore::from_database(db, cache_entries, Some(temp_file))
+    }
+
+    fn from_database(
+        db: Database,
+        cache_entries: usize,
+        temp_file: Option<NamedTempFile>,
+    ) -> Result<Self, StoreError> {
+        // make sure the tables exist before the first read
+        let write_txn = db.begin_write().map_err(redb::Error::from)?;
+        write_txn
+            .open_table(HISTORY_TABLE)
+            .map_err(redb::Error::from)?;
+        {
+            let mut meta = write_txn
+                .open_table(META_TABLE)
+                .map_err(redb::Error::from)?;
+            let version = meta
+                .get("version")
+                .map_err(redb::Error::from)?
+                .map(|version| version.value());
+            match version {
+                Some(version) if version > SCHEMA_VERSION => {
+                    return Err(StoreError::UnsupportedVersion(version, SCHEMA_VERSION));
+                }
+                Some(_) => {}
+                None => {
+                    meta.insert("version", SCHEMA_VERSION)
+                        .map_err(redb::Error::from)?;
+                }
+            }
+        }
+        write_txn.commit().map_err(redb::Error::from)?;
+
+        let cache_entries = NonZeroUsize::new(cache_entries).unwrap_or(NonZeroUsize::MIN);
+
+        Ok(DiskTransactionStore {
+            write_txn: None,
+            db,
+            pending: HashMap::new(),
+            cache: LruCache::new(cache_entries),
+            _temp_file: temp_file,
+        })
+    }
+
+    /// Whether the file holds no transactions, as one left by an earlier run would.
+    pub fn is_empty(&mut self) -> Result<bool, StoreError> {
+        let write_txn = write_txn(&self.db, &mut self.write_txn)?;
+        let stored = write_txn
+            .open_table(HISTORY_TABLE)
+            .map_err(redb::Error::from)?
+            .len()
+            .map_err(redb::Error::from)?;
+
+        Ok(self.pending.is_empty() && stored == 0)
+    }
+
+    // moves the pending inserts into the write transaction, where later reads find them
+    fn flush(&mut self) -> Result<(), StoreError> {
+        let write_txn = write_txn(&self.db, &mut self.write_txn)?;
+        {
+            let mut table = write_txn
+                .open_table(HISTORY_TABLE)
+                .map_err(redb::Error::from)?;
+            let mut key_bytes = Vec::new();
+            let mut value_bytes = Vec::new();
+            for (key, record) in self.pending.iter() {
+                key_bytes.clear();
+                value_bytes.clear();
+                key.encode(&mut key_bytes);
+                record.encode(&mut value_bytes);
+                table
+                    .insert(key_bytes.as_slice(), value_bytes.as_slice())
+                    .map_err(redb::Error::from)?;
+            }
+        }
+
+        for (key, record) in self.pending.drain() {
+            self.cache.put(key, record);
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, key: &TxKey) -> Result<Option<TransactionRecord>, StoreError> {
+        let mut key_bytes = Vec::new();
+        key.encode(&mut key_bytes);
+
+        let write_txn = write_txn(&self.db, &mut self.write_txn)?;
+        let table = write_txn
+            .open_table(HISTORY_TABLE)
+            .map_err(redb::Error::from)?;
+        let record = match table.get(key_bytes.as_slice()).map_err(redb::Error::from)? {
+            Some(value) => {
+                let mut bytes = value.value();
+                Some(TransactionRecord::decode(&mut bytes).ok_or(StoreError::Corrupt)?)
+            }
+            None => None,
+        };
+
+        Ok(record)
+    }
+}
+
+// the open write transaction, begun on first use after opening or committing
+fn write_txn<'a>(
+    db: &Database,
+    write_txn: &'a mut Option<WriteTransaction>,
+) -> Result<&'a WriteTransaction, StoreError> {
+    if write_txn.is_none() {
+        let mut begun = db.begin_write().map_err(redb::Error::from)?;
+        begun.set_durability(Durability::Eventual);
+        *write_txn = Some(begun);
+    }
+
+    // set above
+    Ok(write_txn.as_ref().unwrap())
+}
+
+/**
+ * Pages reads the history in key order, one page of entries at a time, so the entries are
+ * not all held in memory. The table is opened again for every page, as the write
+ * transaction can only lend it out for as long as one read.
+ */
+struct Pages<'a> {
+    write_txn: &'a WriteTransaction,
+    last_key: Option<Vec<u8>>,
+    page: IntoIter<(TxKey, TransactionRecord)>,
+    done: bool,
+}
+
+impl Pages<'_> {
+    fn read_page(&mut self) -> Result<(), StoreError> {
+        let table = self
+            .write_txn
+            .open_table(HISTORY_TABLE)
+            .map_err(redb::Error::from)?;
+        let entries = match &self.last_key {
+            Some(last_key) => {
+                table.range::<&[u8]>((Bound::Excluded(last_key.as_slice()), Bound::Unbounded))
+            }
+            None => table.range::<&[u8]>(..),
+        }
+        .map_err(redb::Error::from)?;
+
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        for entry in entries.take(PAGE_SIZE) {
+            let (key, value) = entry.map_err(redb::Error::from)?;
+            self.last_key = Some(key.value().to_vec());
+            let key = TxKey::decode(&mut key.value()).ok_or(StoreError::Corrupt)?;
+            let record =
+                TransactionRecord::decode(&mut value.value()).ok_or(StoreError::Corrupt)?;
+            page.push((key, record));
+        }
+
+        self.done = page.len() < PAGE_SIZE;
+        self.page = page.into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<(TxKey, TransactionRecord), StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(entry) = self.page.next() {
+            return Some(Ok(entry));
+        }
+        if self.done {
+            return None;
+        }
+
+        match self.read_page() {
+            Ok(()) => self.page.next().map(Ok),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl TransactionStore for DiskTransactionStore {
+    fn contains(&mut self, key: &TxKey) -> Result<bool, StoreError> {
+        if self.pending.contains_key(key) || self.cache.contains(key) {
+            return Ok(true);
+        }
+
+        Ok(self.load(key)?.is_some())
+    }
+
+    fn get(&mut self, key: &TxKey) -> Result<Option<TransactionRecord>, StoreError> {
+        if let Some(record) = self.pending.get(key) {
+            return Ok(Some(record.clone()));
+        }
+        if let Some(record) = self.cache.get(key) {
+            return Ok(Some(record.clone()));
+        }
+
+        let record = self.load(key)?;
+        if let Some(record) = &record {
+            self.cache.put(*key, record.clone());
+        }
+
+        Ok(record)
+    }
+
+    fn upsert(&mut self, key: TxKey, record: TransactionRecord) -> Result<(), StoreError> {
+        self.cache.pop(&key);
+        self.pending.insert(key, record);
+
+        if self.pending.len() >= WRITE_BATCH_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn iter(&mut self) -> Result<TransactionStoreIter<'_>, StoreError> {
+        self.flush()?;
+
+        Ok(Box::new(Pages {
+            write_txn: write_txn(&self.db, &mut self.write_txn)?,
+            last_key: None,
+            page: Vec::new().into_iter(),
+            done: false,
+        }))
+    }
+
+    fn commit(&mut self) -> Result<(), StoreError> {
+        self.flush()?;
+        if let Some(write_txn) = self.write_txn.take() {
+            write_txn.commit().map_err(redb::Error::from)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::{tx_id, ClientId, TransactionId, TransactionType};
+    use crate::models::transaction::Transaction;
+    use crate::stores::transaction_store::DisputeState;
+    use rust_decimal_macros::dec;
+
+    fn deposit(tx_id: TransactionId) -> TransactionRecord {
+        let tx = Transaction::new(tx_id, TransactionType::Deposit, 1, Some(dec!(1.5)));
+        TransactionRecord::new(tx)
+    }
+
+    #[test]
+    fn test_entries_survive_cache_eviction() {
+        let mut store = DiskTransactionStore::temporary(2).unwrap();
+
+        for id in 0..WRITE_BATCH_SIZE as u32 + 10 {
+            store.upsert((tx_id(id), None), deposit(tx_id(id))).unwrap();
+        }
+
+        // flushed to disk and evicted from the cache
+        assert!(store.contains(&(tx_id(0), None)).unwrap());
+        let record = store.get(&(tx_id(0), None)).unwrap().unwrap();
+        assert_eq!(record.get_transaction().get_amount(), Some(dec!(1.5)));
+
+        // still pending
+        let last = tx_id(WRITE_BATCH_SIZE as u32 + 9);
+        assert!(store.get(&(last, None)).unwrap().is_some());
+
+        assert!(!store.contains(&(tx_id(0), Some(1))).unwrap());
+        assert!(store.get(&(tx_id(u32::MAX), None)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unversioned_file_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.redb");
+        {
+            let db = Database::create(&path).unwrap();
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(HISTORY_TABLE).unwrap();
+                let mut key = Vec::new();
+                (tx_id(1), None::<ClientId>).encode(&mut key);
+                let mut value = Vec::new();
+                deposit(tx_id(1)).get_transaction().encode(&mut value);
+                // the disputed flag
+                true.encode(&mut value);
+                table.insert(key.as_slice(), value.as_slice()).unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+
+        let mut store = DiskTransactionStore::open(&path, 1).unwrap();
+
+        let record = store.get(&(tx_id(1), None)).unwrap().unwrap();
+        assert_eq!(record.get_dispute_state(), DisputeState::Disputed);
+    }
+
+    #[test]
+    fn test_newer_version_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.redb");
+        {
+            let db = Database::create(&path).unwrap();
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut meta = write_txn.open_table(META_TABLE).unwrap();
+                meta.insert("version", SCHEMA_VERSION + 1).unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+
+        let result = DiskTransactionStore::open(&path, 1);
+
+        assert!(matches!(
+            result,
+            Err(StoreError::UnsupportedVersion(2, SCHEMA_VERSION))
+        ));
+    }
+
+    #[test]
+    fn test_upsert_and_iterate() {
+        let mut store = DiskTransactionStore::temporary(1).unwrap();
+        store.upsert((tx_id(1), None), deposit(tx_id(1))).unwrap();
+        store.upsert((tx_id(2), None), deposit(tx_id(2))).unwrap();
+
+        let mut disputed = store.get(&(tx_id(1), None)).unwrap().unwrap();
+        disputed.set_dispute_state(DisputeState::Disputed);
+        store.upsert((tx_id(1), None), disputed).unwrap();
+
+        let mut entries = store
+            .iter()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        entries.sort_by_key(|(key, _)| *key);
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].1.is_disputed());
+        assert!(!entries[1].1.is_disputed());
+    }
+
+    #[test]
+    fn test_uncommitted_writes_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.redb");
+        {
+            let mut store = DiskTransactionStore::open(&path, 1).unwrap();
+            for id in 0..WRITE_BATCH_SIZE as u32 + 10 {
+                store.upsert((tx_id(id), None), deposit(tx_id(id))).unwrap();
+            }
+            assert!(store.contains(&(tx_id(0), None)).unwrap());
+        }
+
+        let mut store = DiskTransactionStore::open(&path, 1).unwrap();
+
+        assert!(store.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_committed_writes_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.redb");
+        let count = WRITE_BATCH_SIZE as u32 + 10;
+        {
+            let mut store = DiskTransactionStore::open(&path, 1).unwrap();
+            for id in 0..count {
+                store.upsert((tx_id(id), None), deposit(tx_id(id))).unwrap();
+            }
+            store.commit().unwrap();
+        }
+
+        let mut store = DiskTransactionStore::open(&path, 1).unwrap();
+
+        assert_eq!(store.iter().unwrap().count(), count as usize);
+        assert!(store.contains(&(tx_id(count - 1), None)).unwrap());
+    }
+}
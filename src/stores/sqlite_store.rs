@@ -0,0 +1,505 @@
+use crate::common::types::ClientId;
+use crate::models::client::Client;
+use crate::models::transaction::Transaction;
+use crate::stores::account_store::{AccountStore, AccountStoreIter};
+use crate::stores::store_error::StoreError;
+use crate::stores::transaction_store::{
+    TransactionRecord, TransactionStore, TransactionStoreIter, TxKey,
+};
+use rusqlite::types::{Type, Value};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::vec::IntoIter;
+
+// iterating reads the rows in pages of this size
+const PAGE_SIZE: usize = 1_000;
+
+// kept in `PRAGMA user_version`; 0 is the layout from before the version was recorded
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        client PRIMARY KEY,
+        available TEXT NOT NULL,
+        held TEXT NOT NULL,
+        total TEXT NOT NULL,
+        locked INTEGER NOT NULL
+    );
+    -- key_client is empty when transaction ids are globally unique
+    CREATE TABLE IF NOT EXISTS transactions (
+        tx NOT NULL,
+        key_client TEXT NOT NULL,
+        type TEXT NOT NULL,
+        client NOT NULL,
+        amount TEXT,
+        timestamp,
+        dispute_state TEXT NOT NULL,
+        PRIMARY KEY (tx, key_client)
+    );
+";
+
+// version 0 kept a disputed flag; charged back transactions were stored as not disputed
+// and stay undisputed, their accounts are locked so they cannot be disputed again
+const MIGRATE_DISPUTED_FLAG: &str = "
+    ALTER TABLE transactions ADD COLUMN dispute_state TEXT NOT NULL DEFAULT 'undisputed';
+    UPDATE transactions SET dispute_state = 'disputed' WHERE disputed;
+    ALTER TABLE transactions DROP COLUMN disputed;
+";
+
+/**
+ * Opens (or creates) a SQLite file holding both the accounts and the transaction history,
+ * so the ledger can be inspected with standard SQL tools. The writes are kept in one SQLite
+ * transaction until `commit`, so dropping the stores without it leaves the file as it was.
+ */
+pub fn open_sqlite_stores<P: AsRef<Path>>(
+    path: P,
+) -> Result<(SqliteAccountStore, SqliteTransactionStore), StoreError> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = OFF;")?;
+    conn.execute_batch("BEGIN")?;
+    migrate(&conn)?;
+    conn.execute_batch("COMMIT; BEGIN")?;
+
+    let db = Rc::new(RefCell::new(conn));
+
+    Ok((
+        SqliteAccountStore { db: db.clone() },
+        SqliteTransactionStore { db },
+    ))
+}
+
+// brings a file written by an older build up to the current schema
+fn migrate(conn: &Connection) -> Result<(), StoreError> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(StoreError::UnsupportedVersion(version, SCHEMA_VERSION));
+    }
+
+    if version == 0 {
+        let legacy = conn
+            .prepare("SELECT 1 FROM pragma_table_info('transactions') WHERE name = 'disputed'")?
+            .exists([])?;
+        if legacy {
+            conn.execute_batch(MIGRATE_DISPUTED_FLAG)?;
+        }
+    }
+    conn.execute_batch(SCHEMA)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+    Ok(())
+}
+
+pub struct SqliteAccountStore {
+    db: Rc<RefCell<Connection>>,
+}
+
+pub struct SqliteTransactionStore {
+    db: Rc<RefCell<Connection>>,
+}
+
+// both stores share the connection, so either commits the writes of both
+fn commit(db: &RefCell<Connection>) -> Result<(), StoreError> {
+    db.borrow().execute_batch("COMMIT; BEGIN")?;
+    Ok(())
+}
+
+/**
+ * Pages reads a table in rowid order, one page of rows at a time, so the rows are not all
+ * held in memory. The query takes the last rowid read and the page size, and selects the
+ * rowid after the columns `read` expects.
+ */
+struct Pages<'a, T> {
+    db: &'a RefCell<Connection>,
+    sql: &'static str,
+    read: fn(&Row) -> rusqlite::Result<T>,
+    last_rowid: i64,
+    page: IntoIter<T>,
+    done: bool,
+}
+
+impl<'a, T> Pages<'a, T> {
+    fn new(
+        db: &'a RefCell<Connection>,
+        sql: &'static str,
+        read: fn(&Row) -> rusqlite::Result<T>,
+    ) -> Self {
+        Pages {
+            db,
+            sql,
+            read,
+            last_rowid: 0,
+            page: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    fn read_page(&mut self) -> Result<(), StoreError> {
+        let db = self.db.borrow();
+        let mut stmt = db.prepare_cached(self.sql)?;
+        let mut rows = stmt.query(params![self.last_rowid, PAGE_SIZE])?;
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        while let Some(row) = rows.next()? {
+            self.last_rowid = row.get("rowid")?;
+            page.push((self.read)(row)?);
+        }
+
+        self.done = page.len() < PAGE_SIZE;
+        self.page = page.into_iter();
+        Ok(())
+    }
+}
+
+impl<T> Iterator for Pages<'_, T> {
+    type Item = Result<T, StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.page.next() {
+            return Some(Ok(item));
+        }
+        if self.done {
+            return None;
+        }
+
+        match self.read_page() {
+            Ok(()) => self.page.next().map(Ok),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl AccountStore for SqliteAccountStore {
+    fn get(&self, client_id: ClientId) -> Result<Option<Client>, StoreError> {
+        let db = self.db.borrow();
+        let mut stmt = db.prepare_cached(
+            "SELECT client, available, held, locked FROM accounts WHERE client = ?1",
+        )?;
+        let client = stmt
+            .query_row([id_value(client_id)], read_client)
+            .optional()?;
+
+        Ok(client)
+    }
+
+    fn upsert(&mut self, client: Client) -> Result<(), StoreError> {
+        let db = self.db.borrow();
+        let mut stmt = db.prepare_cached(
+            "INSERT INTO accounts (client, available, held, total, locked)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (client) DO UPDATE SET
+                available = excluded.available,
+                held = excluded.held,
+                total = excluded.total,
+                locked = excluded.locked",
+        )?;
+        stmt.execute(params![
+            id_value(client.get_id()),
+            client.get_available().to_string(),
+            client.get_held().to_string(),
+            (client.get_available() + client.get_held()).to_string(),
+            client.is_locked(),
+        ])?;
+
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<AccountStoreIter<'_>, StoreError> {
+        Ok(Box::new(Pages::new(
+            &self.db,
+            "SELECT client, available, held, locked, rowid FROM accounts
+             WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+            read_client,
+        )))
+    }
+
+    fn commit(&mut self) -> Result<(), StoreError> {
+        commit(&self.db)
+    }
+}
+
+impl TransactionStore for SqliteTransactionStore {
+    fn contains(&mut self, key: &TxKey) -> Result<bool, StoreError> {
+        let db = self.db.borrow();
+        let mut stmt =
+            db.prepare_cached("SELECT 1 FROM transactions WHERE tx = ?1 AND key_client = ?2")?;
+
+        Ok(stmt.exists(params![id_value(key.0), key_client(key)])?)
+    }
+
+    fn get(&mut self, key: &TxKey) -> Result<Option<TransactionRecord>, StoreError> {
+        let db = self.db.borrow();
+        let mut stmt = db.prepare_cached(
+            "SELECT tx, key_client, type, client, amount, timestamp, dispute_state
+             FROM transactions WHERE tx = ?1 AND key_client = ?2",
+        )?;
+        let record = stmt
+            .query_row(params![id_value(key.0), key_client(key)], read_record)
+            .optional()?;
+
+        Ok(record.map(|(_, record)| record))
+    }
+
+    fn upsert(&mut self, key: TxKey, record: TransactionRecord) -> Result<(), StoreError> {
+        let db = self.db.borrow();
+        let tx = record.get_transaction();
+        let mut stmt = db.prepare_cached(
+            "INSERT INTO transactions
+                (tx, key_client, type, client, amount, timestamp, dispute_state)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (tx, key_client) DO UPDATE SET dispute_state = excluded.dispute_state",
+        )?;
+        stmt.execute(params![
+            id_value(key.0),
+            key_client(&key),
+            tx.get_transaction_type().as_str(),
+            id_value(tx.get_client_id()),
+            tx.get_amount().map(|amount| amount.to_string()),
+            tx.get_timestamp().map(id_value),
+            record.get_dispute_state().as_str(),
+        ])?;
+
+        Ok(())
+    }
+
+    fn iter(&mut self) -> Result<TransactionStoreIter<'_>, StoreError> {
+        Ok(Box::new(Pages::new(
+            &self.db,
+            "SELECT tx, key_client, type, client, amount, timestamp, dispute_state, rowid
+             FROM transactions WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+            read_record,
+        )))
+    }
+
+    fn commit(&mut self) -> Result<(), StoreError> {
+        commit(&self.db)
+    }
+}
+
+// ids are stored as integers where possible so they are easy to query
+fn id_value<T: Display>(id: T) -> Value {
+    let text = id.to_string();
+    match text.parse::<i64>() {
+        Ok(number) if number.to_string() == text => Value::Integer(number),
+        _ => Value::Text(text),
+    }
+}
+
+fn key_client(key: &TxKey) -> String {
+    key.1
+        .map(|client_id| client_id.to_string())
+        .unwrap_or_default()
+}
+
+fn get_parsed<T: FromStr>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    let text = match row.get::<_, Value>(idx)? {
+        Value::Integer(number) => number.to_string(),
+        Value::Text(text) => text,
+        other => {
+            let err = "expected an integer or text".into();
+            return Err(rusqlite::Error::FromSqlConversionFailure(
+                idx,
+                other.data_type(),
+                err,
+            ));
+        }
+    };
+
+    text.parse().map_err(|_| {
+        let err = format!("invalid value '{}'", text).into();
+        rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, err)
+    })
+}
+
+fn read_client(row: &Row) -> rusqlite::Result<Client> {
+    Ok(Client::restore(
+        get_parsed(row, 0)?,
+        get_parsed(row, 1)?,
+        get_parsed(row, 2)?,
+        row.get(3)?,
+    ))
+}
+
+fn read_record(row: &Row) -> rusqlite::Result<(TxKey, TransactionRecord)> {
+    let tx_id = get_parsed(row, 0)?;
+    let key_client = match row.get::<_, String>(1)?.as_str() {
+        "" => None,
+        _ => Some(get_parsed(row, 1)?),
+    };
+    let amount = match row.get::<_, Option<String>>(4)? {
+        Some(_) => Some(get_parsed(row, 4)?),
+        None => None,
+    };
+
+    let mut tx = Transaction::new(tx_id, get_parsed(row, 2)?, get_parsed(row, 3)?, amount);
+    if row.get::<_, Option<Value>>(5)?.is_some() {
+        tx = tx.with_timestamp(get_parsed(row, 5)?);
+    }
+    let mut record = TransactionRecord::new(tx);
+    record.set_dispute_state(get_parsed(row, 6)?);
+
+    Ok(((tx_id, key_client), record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::{tx_id, TransactionType};
+    use crate::stores::transaction_store::DisputeState;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_accounts_and_transactions_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        let (mut accounts, mut transactions) = open_sqlite_stores(&path).unwrap();
+
+        let client = Client::restore(3, dec!(1.5), dec!(2.25), true);
+        accounts.upsert(client.clone()).unwrap();
+        let tx = Transaction::new(tx_id(7), TransactionType::Deposit, 3, Some(dec!(2.25)))
+            .with_timestamp(1700000000);
+        let mut record = TransactionRecord::new(tx);
+        transactions
+            .upsert((tx_id(7), None), record.clone())
+            .unwrap();
+        record.set_dispute_state(DisputeState::Disputed);
+        transactions
+            .upsert((tx_id(7), None), record.clone())
+            .unwrap();
+
+        assert_eq!(accounts.get(3).unwrap(), Some(client.clone()));
+        assert_eq!(accounts.get(4).unwrap(), None);
+        assert!(transactions.contains(&(tx_id(7), None)).unwrap());
+        assert!(!transactions.contains(&(tx_id(7), Some(3))).unwrap());
+        assert_eq!(
+            transactions.get(&(tx_id(7), None)).unwrap(),
+            Some(record.clone())
+        );
+
+        let clients = accounts.iter().unwrap().collect::<Result<Vec<_>, _>>();
+        assert_eq!(clients.unwrap(), vec![client]);
+        let records = transactions.iter().unwrap().collect::<Result<Vec<_>, _>>();
+        assert_eq!(records.unwrap(), vec![((tx_id(7), None), record)]);
+    }
+
+    #[test]
+    fn test_unversioned_file_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE transactions (
+                    tx NOT NULL,
+                    key_client TEXT NOT NULL,
+                    type TEXT NOT NULL,
+                    client NOT NULL,
+                    amount TEXT,
+                    timestamp,
+                    disputed INTEGER NOT NULL,
+                    PRIMARY KEY (tx, key_client)
+                );
+                INSERT INTO transactions VALUES (1, '', 'deposit', 3, '2.5', NULL, 1);
+                INSERT INTO transactions VALUES (2, '', 'deposit', 3, '1.0', NULL, 0);",
+            )
+            .unwrap();
+        }
+
+        let (_, mut transactions) = open_sqlite_stores(&path).unwrap();
+
+        let disputed = transactions.get(&(tx_id(1), None)).unwrap().unwrap();
+        assert_eq!(disputed.get_dispute_state(), DisputeState::Disputed);
+        let undisputed = transactions.get(&(tx_id(2), None)).unwrap().unwrap();
+        assert_eq!(undisputed.get_dispute_state(), DisputeState::Undisputed);
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        let result = open_sqlite_stores(&path);
+
+        assert!(matches!(
+            result,
+            Err(StoreError::UnsupportedVersion(2, SCHEMA_VERSION))
+        ));
+    }
+
+    #[test]
+    fn test_ledger_is_readable_with_sql() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        {
+            let (mut accounts, _) = open_sqlite_stores(&path).unwrap();
+            accounts
+                .upsert(Client::restore(3, dec!(1.5), dec!(0), false))
+                .unwrap();
+            accounts.commit().unwrap();
+        }
+
+        let conn = Connection::open(&path).unwrap();
+        let total: String = conn
+            .query_row("SELECT total FROM accounts WHERE client = 3", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+
+        assert_eq!(total, "1.5");
+    }
+
+    #[test]
+    fn test_uncommitted_writes_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        {
+            let (mut accounts, mut transactions) = open_sqlite_stores(&path).unwrap();
+            accounts
+                .upsert(Client::restore(3, dec!(1.5), dec!(0), false))
+                .unwrap();
+            transactions.commit().unwrap();
+            accounts
+                .upsert(Client::restore(4, dec!(2), dec!(0), false))
+                .unwrap();
+        }
+
+        let (accounts, _) = open_sqlite_stores(&path).unwrap();
+
+        assert!(accounts.get(3).unwrap().is_some());
+        assert_eq!(accounts.get(4).unwrap(), None);
+    }
+
+    #[test]
+    fn test_iter_reads_every_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut accounts, mut transactions) =
+            open_sqlite_stores(dir.path().join("ledger.sqlite")).unwrap();
+        let count = PAGE_SIZE as u32 * 2 + 1;
+        for id in 1..=count {
+            let client_id = (id % 100) as ClientId;
+            accounts
+                .upsert(Client::restore(client_id, dec!(1), dec!(0), false))
+                .unwrap();
+            let tx = Transaction::new(tx_id(id), TransactionType::Deposit, client_id, None);
+            transactions
+                .upsert((tx_id(id), None), TransactionRecord::new(tx))
+                .unwrap();
+        }
+
+        let clients = accounts.iter().unwrap().collect::<Result<Vec<_>, _>>();
+        let records = transactions.iter().unwrap().collect::<Result<Vec<_>, _>>();
+
+        assert_eq!(clients.unwrap().len(), 100);
+        let records = records.unwrap();
+        assert_eq!(records.len(), count as usize);
+        assert_eq!(records.last().unwrap().0, (tx_id(count), None));
+    }
+}
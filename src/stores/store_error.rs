@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("failed to access the store file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to access the store database: {0}")]
+    Database(Box<redb::Error>),
+
+    #[error("failed to access the SQLite database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("store entry could not be decoded")]
+    Corrupt,
+
+    #[error("store has schema version {0}, but this build reads up to version {1}")]
+    UnsupportedVersion(u32, u32),
+
+    #[error("{0} already holds a transaction history, but the accounts that go with it are not kept between runs")]
+    HistoryNotEmpty(PathBuf),
+}
+
+impl From<redb::Error> for StoreError {
+    fn from(err: redb::Error) -> Self {
+        StoreError::Database(Box::new(err))
+    }
+}
@@ -0,0 +1,133 @@
+use crate::common::types::{ClientId, TransactionId};
+use crate::models::transaction::Transaction;
+use crate::stores::store_error::StoreError;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Where a deposit or withdrawal is in the dispute process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisputeState {
+    #[default]
+    Undisputed,
+    Disputed,
+    ChargedBack,
+}
+
+impl DisputeState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisputeState::Undisputed => "undisputed",
+            DisputeState::Disputed => "disputed",
+            DisputeState::ChargedBack => "charged_back",
+        }
+    }
+
+    /// A second dispute would hold the same funds twice, and a charged back transaction has
+    /// already been paid back, so only undisputed transactions can be disputed.
+    pub fn can_dispute(&self) -> bool {
+        *self == DisputeState::Undisputed
+    }
+}
+
+impl FromStr for DisputeState {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "undisputed" => Ok(DisputeState::Undisputed),
+            "disputed" => Ok(DisputeState::Disputed),
+            "charged_back" => Ok(DisputeState::ChargedBack),
+            _ => Err(()),
+        }
+    }
+}
+
+/// With globally unique transaction ids the client is left out of the key.
+pub type TxKey = (TransactionId, Option<ClientId>);
+
+/**
+ * TransactionRecord is a deposit or withdrawal as kept in the history, together with
+ * its dispute state.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionRecord {
+    transaction: Transaction,
+    dispute_state: DisputeState,
+}
+
+impl TransactionRecord {
+    pub fn new(transaction: Transaction) -> Self {
+        TransactionRecord {
+            transaction,
+            dispute_state: DisputeState::Undisputed,
+        }
+    }
+
+    pub fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn get_dispute_state(&self) -> DisputeState {
+        self.dispute_state
+    }
+
+    pub fn is_disputed(&self) -> bool {
+        self.dispute_state == DisputeState::Disputed
+    }
+
+    pub fn set_dispute_state(&mut self, dispute_state: DisputeState) {
+        self.dispute_state = dispute_state;
+    }
+}
+
+pub type TransactionStoreIter<'a> =
+    Box<dyn Iterator<Item = Result<(TxKey, TransactionRecord), StoreError>> + 'a>;
+
+/**
+ * TransactionStore keeps the deposits and withdrawals that may still be disputed.
+ */
+pub trait TransactionStore {
+    fn contains(&mut self, key: &TxKey) -> Result<bool, StoreError>;
+    fn get(&mut self, key: &TxKey) -> Result<Option<TransactionRecord>, StoreError>;
+    fn upsert(&mut self, key: TxKey, record: TransactionRecord) -> Result<(), StoreError>;
+    fn iter(&mut self) -> Result<TransactionStoreIter<'_>, StoreError>;
+
+    /// Makes the writes so far permanent, once a run has gone through all of its rows.
+    fn commit(&mut self) -> Result<(), StoreError> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryTransactionStore {
+    transactions: HashMap<TxKey, TransactionRecord>,
+}
+
+impl MemoryTransactionStore {
+    pub fn new() -> Self {
+        MemoryTransactionStore::default()
+    }
+}
+
+impl TransactionStore for MemoryTransactionStore {
+    fn contains(&mut self, key: &TxKey) -> Result<bool, StoreError> {
+        Ok(self.transactions.contains_key(key))
+    }
+
+    fn get(&mut self, key: &TxKey) -> Result<Option<TransactionRecord>, StoreError> {
+        Ok(self.transactions.get(key).cloned())
+    }
+
+    fn upsert(&mut self, key: TxKey, record: TransactionRecord) -> Result<(), StoreError> {
+        self.transactions.insert(key, record);
+        Ok(())
+    }
+
+    fn iter(&mut self) -> Result<TransactionStoreIter<'_>, StoreError> {
+        let entries = self
+            .transactions
+            .iter()
+            .map(|(key, record)| Ok((*key, record.clone())));
+        Ok(Box::new(entries))
+    }
+}
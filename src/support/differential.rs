@@ -0,0 +1,140 @@
+use crate::managers::transaction_manager::{TransactionManager, TxError};
+use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::transaction::Transaction;
+use crate::stores::account_store::AccountStore;
+use crate::stores::transaction_store::TransactionStore;
+use crate::support::reference::ReferenceLedger;
+use std::fmt;
+
+/**
+ * Divergence is the first transaction on which `TransactionManager` and `ReferenceLedger`
+ * disagree, either about accepting it or about the account it leaves behind.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// 1-based, counting rejected transactions too.
+    pub position: u64,
+    pub transaction: Transaction,
+    pub engine_result: Result<(), TxError>,
+    pub engine_account: Option<ClientSnapshot>,
+    pub reference_accepted: bool,
+    pub reference_account: Option<ClientSnapshot>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tx = &self.transaction;
+        let engine_outcome = match &self.engine_result {
+            Ok(()) => "accepted".to_string(),
+            Err(err) => format!("rejected ({})", err),
+        };
+        let reference_outcome = match self.reference_accepted {
+            true => "accepted",
+            false => "rejected",
+        };
+
+        writeln!(
+            f,
+            "transaction {} ({} of client {}, tx {}, amount {:?}) diverges",
+            self.position,
+            tx.get_transaction_type().as_str(),
+            tx.get_client_id(),
+            tx.get_transaction_id(),
+            tx.get_amount()
+        )?;
+        writeln!(
+            f,
+            "  engine:    {}, account {:?}",
+            engine_outcome, self.engine_account
+        )?;
+        write!(
+            f,
+            "  reference: {}, account {:?}",
+            reference_outcome, self.reference_account
+        )
+    }
+}
+
+/// Runs the transactions through `manager` and the reference ledger side by side and stops at
+/// the first one they disagree on. Only a broken store is returned as an error.
+pub fn first_divergence<A, T, I>(
+    mut manager: TransactionManager<A, T>,
+    transactions: I,
+) -> Result<Option<Divergence>, TxError>
+where
+    A: AccountStore,
+    T: TransactionStore,
+    I: IntoIterator<Item = Transaction>,
+{
+    let mut reference = ReferenceLedger::new();
+
+    for (index, tx) in transactions.into_iter().enumerate() {
+        let client_id = tx.get_client_id();
+        let engine_result = manager.add_transaction(tx.clone());
+        if let Err(TxError::StoreFailure(err)) = engine_result {
+            return Err(TxError::StoreFailure(err));
+        }
+        let reference_accepted = reference.apply(&tx);
+
+        let engine_account = manager.get_snapshot(client_id)?;
+        let reference_account = reference.get_account(client_id);
+        if engine_result.is_ok() != reference_accepted || engine_account != reference_account {
+            return Ok(Some(Divergence {
+                position: index as u64 + 1,
+                transaction: tx,
+                engine_result,
+                engine_account,
+                reference_accepted,
+                reference_account,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::tx_id;
+    use crate::common::types::TransactionType::{Deposit, Dispute, Withdrawal};
+    use rust_decimal_macros::dec;
+    use Transaction as Tx;
+
+    #[test]
+    fn test_agreeing_stream() {
+        let transactions = vec![
+            Tx::new(tx_id(1), Deposit, 1, Some(dec!(2.0))),
+            Tx::new(tx_id(2), Withdrawal, 1, Some(dec!(3.0))),
+            Tx::new(tx_id(1), Dispute, 1, None),
+        ];
+
+        let divergence = first_divergence(TransactionManager::new(), transactions);
+
+        assert_eq!(divergence, Ok(None));
+    }
+
+    #[test]
+    fn test_first_divergence_is_reported() {
+        // the reference ledger only knows globally unique transaction ids
+        let manager = TransactionManager::new().with_global_tx_ids(false);
+        let transactions = vec![
+            Tx::new(tx_id(1), Deposit, 1, Some(dec!(2.0))),
+            Tx::new(tx_id(1), Deposit, 2, Some(dec!(3.0))),
+            Tx::new(tx_id(1), Deposit, 3, Some(dec!(4.0))),
+        ];
+
+        let divergence = first_divergence(manager, transactions).unwrap().unwrap();
+
+        assert_eq!(divergence.position, 2);
+        assert_eq!(divergence.engine_result, Ok(()));
+        assert!(!divergence.reference_accepted);
+        assert_eq!(
+            divergence.reference_account,
+            Some(ClientSnapshot::new(2, dec!(0), dec!(0), false))
+        );
+        assert!(divergence
+            .to_string()
+            .starts_with("transaction 2 (deposit of client 2, tx 1"));
+    }
+}
@@ -0,0 +1,503 @@
+use crate::common::types::{ClientId, TransactionId, TransactionType};
+use crate::models::transaction::Transaction;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+// transactions that later rows may dispute or duplicate
+const RECENT_CAPACITY: usize = 1024;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum GeneratorError {
+    #[error("The {0} rate must be between 0 and 1")]
+    InvalidRate(&'static str),
+
+    #[error("The rates add up to more than 1")]
+    RatesTooHigh,
+
+    #[error("{0} clients do not fit the client id type")]
+    TooManyClients(u64),
+
+    #[error("{0} rows do not fit the transaction id type")]
+    TooManyRows(u64),
+}
+
+/// What `TransactionGenerator` produces. The rates are the share of rows of each kind.
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+    pub clients: u64,
+    pub rows: u64,
+    /// Disputes of earlier deposits and withdrawals. About as many rows close a dispute again.
+    pub dispute_rate: f64,
+    /// The share of closed disputes that end in a chargeback rather than a resolve.
+    pub chargeback_rate: f64,
+    /// Well-formed rows the ledger has to reject, e.g. overdrawing withdrawals or disputes of
+    /// unknown transactions. Other withdrawals stay within the client's balance, so without
+    /// disputes and duplicates these are the only rejected rows.
+    pub rejection_rate: f64,
+    /// Repeats of an earlier deposit or withdrawal with the same transaction id.
+    pub duplicate_rate: f64,
+    /// Rows the parser has to refuse, e.g. an unknown type, a missing field or an amount that
+    /// is not a number. They only appear in `TransactionGenerator::rows`, on top of `rows`.
+    pub malformed_rate: f64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            seed: 1,
+            clients: 100,
+            rows: 10_000,
+            dispute_rate: 0.02,
+            chargeback_rate: 0.2,
+            rejection_rate: 0.01,
+            duplicate_rate: 0.01,
+            malformed_rate: 0.0,
+        }
+    }
+}
+
+// SplitMix64, small and stable so a seed always produces the same stream
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    // uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// what the generator has sent a client so far
+#[derive(Default)]
+struct ModeledClient {
+    // deposits less withdrawals, the available balance as long as nothing is disputed
+    balance: Decimal,
+    // disputes and chargebacks can only lower the available balance below this
+    deposited: Decimal,
+}
+
+/**
+ * TransactionGenerator produces a reproducible stream of transactions for a seed.
+ */
+pub struct TransactionGenerator {
+    config: GeneratorConfig,
+    rng: Rng,
+    emitted: u64,
+    next_tx_id: u64,
+    // never handed out, so referring to it is always an error
+    unknown_tx_id: u64,
+    recent: Vec<Transaction>,
+    open_disputes: Vec<Transaction>,
+    clients: HashMap<u64, ModeledClient>,
+}
+
+impl TransactionGenerator {
+    pub fn new(config: GeneratorConfig) -> Result<Self, GeneratorError> {
+        let rates = [
+            ("dispute", config.dispute_rate),
+            ("chargeback", config.chargeback_rate),
+            ("rejection", config.rejection_rate),
+            ("duplicate", config.duplicate_rate),
+        ];
+        for (name, rate) in rates {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(GeneratorError::InvalidRate(name));
+            }
+        }
+        // a malformed rate of 1 would never get to the transactions
+        if !(0.0..1.0).contains(&config.malformed_rate) {
+            return Err(GeneratorError::InvalidRate("malformed"));
+        }
+        if 2.0 * config.dispute_rate + config.rejection_rate + config.duplicate_rate > 1.0 {
+            return Err(GeneratorError::RatesTooHigh);
+        }
+        if config.clients == 0 || parse_id::<ClientId>(config.clients).is_none() {
+            return Err(GeneratorError::TooManyClients(config.clients));
+        }
+        let unknown_tx_id = largest_id::<TransactionId>();
+        if config.rows >= unknown_tx_id {
+            return Err(GeneratorError::TooManyRows(config.rows));
+        }
+
+        Ok(TransactionGenerator {
+            rng: Rng(config.seed),
+            config,
+            emitted: 0,
+            next_tx_id: 1,
+            unknown_tx_id,
+            recent: Vec::new(),
+            open_disputes: Vec::new(),
+            clients: HashMap::new(),
+        })
+    }
+
+    fn client(&mut self) -> u64 {
+        self.rng.below(self.config.clients) + 1
+    }
+
+    // amounts with four decimal places up to `max` units
+    fn amount(&mut self, max: u64) -> Decimal {
+        Decimal::new((self.rng.below(max * 10_000) + 1) as i64, 4)
+    }
+
+    fn transfer(&mut self) -> Transaction {
+        let client = self.client();
+        let tx_id = self.next_tx_id;
+        self.next_tx_id += 1;
+
+        let withdrawal = if self.rng.below(3) < 2 {
+            None
+        } else {
+            Some(self.amount(500))
+        };
+        let balance = self
+            .clients
+            .get(&client)
+            .map_or(Decimal::ZERO, |c| c.balance);
+        let tx = match withdrawal {
+            // a withdrawal the balance does not cover becomes a deposit
+            Some(amount) if amount <= balance => {
+                self.clients.entry(client).or_default().balance -= amount;
+                transaction(tx_id, TransactionType::Withdrawal, client, Some(amount))
+            }
+            _ => {
+                let amount = self.amount(1000);
+                let modeled = self.clients.entry(client).or_default();
+                modeled.balance += amount;
+                modeled.deposited += amount;
+                transaction(tx_id, TransactionType::Deposit, client, Some(amount))
+            }
+        };
+
+        if self.recent.len() < RECENT_CAPACITY {
+            self.recent.push(tx.clone());
+        } else {
+            let slot = self.rng.below(RECENT_CAPACITY as u64) as usize;
+            self.recent[slot] = tx.clone();
+        }
+        tx
+    }
+
+    fn pick_recent(&mut self) -> Option<Transaction> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let index = self.rng.below(self.recent.len() as u64) as usize;
+        Some(self.recent[index].clone())
+    }
+
+    fn rejected(&mut self) -> Transaction {
+        let client = self.client();
+        match self.rng.below(3) {
+            0 => {
+                let tx_id = self.next_tx_id;
+                self.next_tx_id += 1;
+                let deposited = self
+                    .clients
+                    .get(&client)
+                    .map_or(Decimal::ZERO, |c| c.deposited);
+                let amount = deposited + self.amount(1000);
+                transaction(tx_id, TransactionType::Withdrawal, client, Some(amount))
+            }
+            1 => transaction(self.unknown_tx_id, TransactionType::Dispute, client, None),
+            _ => transaction(
+                self.unknown_tx_id,
+                TransactionType::Chargeback,
+                client,
+                None,
+            ),
+        }
+    }
+
+    /// The stream as input rows, with malformed rows mixed in at `malformed_rate`. The
+    /// transactions are the same as the ones the iterator yields.
+    pub fn rows(self) -> GeneratedRows {
+        GeneratedRows {
+            // a stream of its own, so the transactions do not depend on the malformed rate
+            rng: Rng(!self.config.seed),
+            generator: self,
+        }
+    }
+
+    fn dispute(&mut self) -> Option<Transaction> {
+        let disputed = self.pick_recent()?;
+        self.open_disputes.push(disputed.clone());
+        Some(follow_up(&disputed, TransactionType::Dispute))
+    }
+
+    fn close_dispute(&mut self) -> Option<Transaction> {
+        if self.open_disputes.is_empty() {
+            return None;
+        }
+        let index = self.rng.below(self.open_disputes.len() as u64) as usize;
+        let disputed = self.open_disputes.swap_remove(index);
+
+        let tx_type = if self.rng.unit() < self.config.chargeback_rate {
+            TransactionType::Chargeback
+        } else {
+            TransactionType::Resolve
+        };
+        Some(follow_up(&disputed, tx_type))
+    }
+}
+
+impl Iterator for TransactionGenerator {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.emitted >= self.config.rows {
+            return None;
+        }
+        self.emitted += 1;
+
+        let config = &self.config;
+        let rejected = config.rejection_rate;
+        let duplicate = rejected + config.duplicate_rate;
+        let dispute = duplicate + config.dispute_rate;
+        let close = dispute + config.dispute_rate;
+
+        let roll = self.rng.unit();
+        let tx = if roll < rejected {
+            Some(self.rejected())
+        } else if roll < duplicate {
+            self.pick_recent()
+        } else if roll < dispute {
+            self.dispute()
+        } else if roll < close {
+            self.close_dispute()
+        } else {
+            None
+        };
+
+        // early on there may be nothing to duplicate or dispute yet
+        Some(tx.unwrap_or_else(|| self.transfer()))
+    }
+}
+
+/// A row of a generated input file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeneratedRow {
+    Transaction(Transaction),
+    /// The fields of a row the parser has to refuse.
+    Malformed(Vec<String>),
+}
+
+/**
+ * GeneratedRows yields the transactions of a `TransactionGenerator` with malformed rows
+ * mixed in.
+ */
+pub struct GeneratedRows {
+    generator: TransactionGenerator,
+    rng: Rng,
+}
+
+impl GeneratedRows {
+    fn malformed(&mut self) -> Vec<String> {
+        let client = (self.rng.below(self.generator.config.clients) + 1).to_string();
+        let tx_id = self.generator.unknown_tx_id.to_string();
+        let fields = match self.rng.below(5) {
+            0 => vec!["transfer", &client, &tx_id, "1.0"],
+            1 => vec!["deposit", "client", &tx_id, "1.0"],
+            2 => vec!["deposit", &client],
+            3 => vec!["deposit", &client, &tx_id, "1.0", "1.0"],
+            _ => vec!["deposit", &client, &tx_id, "1.0.0"],
+        };
+        fields.into_iter().map(String::from).collect()
+    }
+}
+
+impl Iterator for GeneratedRows {
+    type Item = GeneratedRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // no malformed rows after the last transaction
+        if self.generator.emitted >= self.generator.config.rows {
+            return None;
+        }
+        if self.rng.unit() < self.generator.config.malformed_rate {
+            return Some(GeneratedRow::Malformed(self.malformed()));
+        }
+        self.generator.next().map(GeneratedRow::Transaction)
+    }
+}
+
+// the ids are checked against the configured width when the generator is created
+fn parse_id<T: FromStr>(value: u64) -> Option<T> {
+    value.to_string().parse().ok()
+}
+
+// the largest u64 the id type can hold, found by bisection as the id types only share FromStr
+fn largest_id<T: FromStr>() -> u64 {
+    if parse_id::<T>(u64::MAX).is_some() {
+        return u64::MAX;
+    }
+    let (mut fits, mut too_large) = (0, u64::MAX);
+    while too_large - fits > 1 {
+        let middle = fits + (too_large - fits) / 2;
+        if parse_id::<T>(middle).is_some() {
+            fits = middle;
+        } else {
+            too_large = middle;
+        }
+    }
+    fits
+}
+
+fn transaction(
+    tx_id: u64,
+    tx_type: TransactionType,
+    client: u64,
+    amount: Option<Decimal>,
+) -> Transaction {
+    match (parse_id(tx_id), parse_id(client)) {
+        (Some(tx_id), Some(client)) => Transaction::new(tx_id, tx_type, client, amount),
+        _ => unreachable!("ids are validated in TransactionGenerator::new"),
+    }
+}
+
+fn follow_up(disputed: &Transaction, tx_type: TransactionType) -> Transaction {
+    Transaction::new(
+        disputed.get_transaction_id(),
+        tx_type,
+        disputed.get_client_id(),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::transaction_manager::TransactionManager;
+
+    fn generate(config: GeneratorConfig) -> Vec<Transaction> {
+        TransactionGenerator::new(config).unwrap().collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_stream() {
+        let config = GeneratorConfig::default();
+        let other_seed = GeneratorConfig {
+            seed: 2,
+            ..GeneratorConfig::default()
+        };
+
+        let stream = generate(config.clone());
+
+        assert_eq!(stream.len(), 10_000);
+        assert_eq!(stream, generate(config));
+        assert_ne!(stream, generate(other_seed));
+    }
+
+    #[test]
+    fn test_rates() {
+        let config = GeneratorConfig {
+            rows: 1000,
+            dispute_rate: 0.0,
+            rejection_rate: 0.0,
+            duplicate_rate: 0.0,
+            ..GeneratorConfig::default()
+        };
+
+        let stream = generate(config);
+
+        assert!(stream.iter().all(|tx| matches!(
+            tx.get_transaction_type(),
+            TransactionType::Deposit | TransactionType::Withdrawal
+        )));
+    }
+
+    #[test]
+    fn test_rejection_rate() {
+        let config = GeneratorConfig {
+            dispute_rate: 0.0,
+            rejection_rate: 0.1,
+            duplicate_rate: 0.0,
+            ..GeneratorConfig::default()
+        };
+        let mut manager = TransactionManager::new();
+
+        let rejected = generate(config)
+            .into_iter()
+            .filter(|tx| manager.add_transaction(tx.clone()).is_err())
+            .count();
+
+        assert!((900..1100).contains(&rejected), "{}", rejected);
+    }
+
+    #[test]
+    fn test_largest_id() {
+        assert_eq!(largest_id::<u8>(), 255);
+        assert_eq!(largest_id::<u32>(), u32::MAX as u64);
+        assert_eq!(largest_id::<u64>(), u64::MAX);
+    }
+
+    #[test]
+    fn test_malformed_rows() {
+        let config = GeneratorConfig {
+            rows: 1000,
+            malformed_rate: 0.1,
+            ..GeneratorConfig::default()
+        };
+        let generator = TransactionGenerator::new(config.clone()).unwrap();
+
+        let rows: Vec<GeneratedRow> = generator.rows().collect();
+
+        let transactions: Vec<Transaction> = rows
+            .iter()
+            .filter_map(|row| match row {
+                GeneratedRow::Transaction(tx) => Some(tx.clone()),
+                GeneratedRow::Malformed(_) => None,
+            })
+            .collect();
+        assert_eq!(transactions, generate(config));
+        let malformed = rows.len() - transactions.len();
+        assert!((50..200).contains(&malformed), "{}", malformed);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = GeneratorConfig {
+            dispute_rate: 1.5,
+            ..GeneratorConfig::default()
+        };
+        let result = TransactionGenerator::new(config);
+        assert_eq!(result.err(), Some(GeneratorError::InvalidRate("dispute")));
+
+        let config = GeneratorConfig {
+            dispute_rate: 0.5,
+            rejection_rate: 0.5,
+            ..GeneratorConfig::default()
+        };
+        let result = TransactionGenerator::new(config);
+        assert_eq!(result.err(), Some(GeneratorError::RatesTooHigh));
+
+        let config = GeneratorConfig {
+            malformed_rate: 1.0,
+            ..GeneratorConfig::default()
+        };
+        let result = TransactionGenerator::new(config);
+        assert_eq!(result.err(), Some(GeneratorError::InvalidRate("malformed")));
+
+        let rows = largest_id::<TransactionId>();
+        let config = GeneratorConfig {
+            rows,
+            ..GeneratorConfig::default()
+        };
+        let result = TransactionGenerator::new(config);
+        assert_eq!(result.err(), Some(GeneratorError::TooManyRows(rows)));
+    }
+}
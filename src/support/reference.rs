@@ -0,0 +1,225 @@
+use crate::common::types::{ClientId, TransactionId, TransactionType};
+use crate::models::client_snapshot::ClientSnapshot;
+use crate::models::transaction::Transaction;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Default)]
+struct Account {
+    available: Decimal,
+    held: Decimal,
+    locked: bool,
+}
+
+impl Account {
+    // applies both balances or neither; the total has to fit as well
+    fn update(&mut self, available: Option<Decimal>, held: Option<Decimal>) -> bool {
+        match (available, held) {
+            (Some(available), Some(held)) if available.checked_add(held).is_some() => {
+                self.available = available;
+                self.held = held;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum State {
+    Settled,
+    Disputed,
+    ChargedBack,
+}
+
+struct Entry {
+    client_id: ClientId,
+    is_deposit: bool,
+    amount: Decimal,
+    state: State,
+}
+
+/**
+ * ReferenceLedger is a deliberately plain implementation of the ledger rules. It shares
+ * nothing with `TransactionManager` but the input types, so the two can be checked against
+ * each other. It only knows the default rules: globally unique transaction ids and no
+ * dispute window.
+ */
+#[derive(Default)]
+pub struct ReferenceLedger {
+    accounts: BTreeMap<ClientId, Account>,
+    entries: HashMap<TransactionId, Entry>,
+}
+
+impl ReferenceLedger {
+    pub fn new() -> Self {
+        ReferenceLedger::default()
+    }
+
+    /// Applies a transaction and tells whether it was accepted.
+    pub fn apply(&mut self, tx: &Transaction) -> bool {
+        let client_id = tx.get_client_id();
+        let tx_id = tx.get_transaction_id();
+
+        // an account exists as soon as a client shows up, even if its transaction is rejected
+        let account = self.accounts.entry(client_id).or_default();
+        if account.locked {
+            return false;
+        }
+
+        match tx.get_transaction_type() {
+            TransactionType::Deposit | TransactionType::Withdrawal => {
+                let is_deposit = tx.get_transaction_type() == TransactionType::Deposit;
+                let amount = match tx.get_amount() {
+                    Some(amount) => amount,
+                    None => return false,
+                };
+                if self.entries.contains_key(&tx_id) {
+                    return false;
+                }
+
+                let accepted = if is_deposit {
+                    account.update(account.available.checked_add(amount), Some(account.held))
+                } else {
+                    account.available >= amount
+                        && account.update(account.available.checked_sub(amount), Some(account.held))
+                };
+                if accepted {
+                    let entry = Entry {
+                        client_id,
+                        is_deposit,
+                        amount,
+                        state: State::Settled,
+                    };
+                    self.entries.insert(tx_id, entry);
+                }
+                accepted
+            }
+            TransactionType::Dispute => {
+                let entry = match self.entries.get_mut(&tx_id) {
+                    Some(entry) if entry.client_id == client_id => entry,
+                    _ => return false,
+                };
+                if entry.state != State::Settled {
+                    return false;
+                }
+
+                let amount = entry.amount;
+                let accepted = if entry.is_deposit {
+                    account.update(
+                        account.available.checked_sub(amount),
+                        account.held.checked_add(amount),
+                    )
+                } else {
+                    account.update(Some(account.available), account.held.checked_add(amount))
+                };
+                if accepted {
+                    entry.state = State::Disputed;
+                }
+                accepted
+            }
+            TransactionType::Resolve => {
+                let entry = match self.entries.get_mut(&tx_id) {
+                    Some(entry) if entry.client_id == client_id => entry,
+                    _ => return false,
+                };
+                if entry.state != State::Disputed {
+                    return false;
+                }
+
+                let amount = entry.amount;
+                let accepted = if entry.is_deposit {
+                    account.update(
+                        account.available.checked_add(amount),
+                        account.held.checked_sub(amount),
+                    )
+                } else {
+                    account.update(Some(account.available), account.held.checked_sub(amount))
+                };
+                if accepted {
+                    entry.state = State::Settled;
+                }
+                accepted
+            }
+            TransactionType::Chargeback => {
+                let entry = match self.entries.get_mut(&tx_id) {
+                    Some(entry) if entry.client_id == client_id => entry,
+                    _ => return false,
+                };
+                if entry.state != State::Disputed {
+                    return false;
+                }
+
+                let amount = entry.amount;
+                let accepted = if entry.is_deposit {
+                    account.update(Some(account.available), account.held.checked_sub(amount))
+                } else {
+                    account.update(
+                        account.available.checked_add(amount),
+                        account.held.checked_sub(amount),
+                    )
+                };
+                if accepted {
+                    entry.state = State::ChargedBack;
+                    account.locked = true;
+                }
+                accepted
+            }
+        }
+    }
+
+    pub fn get_account(&self, client_id: ClientId) -> Option<ClientSnapshot> {
+        self.accounts.get(&client_id).map(|account| {
+            ClientSnapshot::new(client_id, account.available, account.held, account.locked)
+        })
+    }
+
+    /// The accounts ordered by client id.
+    pub fn get_accounts(&self) -> Vec<ClientSnapshot> {
+        self.accounts
+            .iter()
+            .map(|(client_id, account)| {
+                ClientSnapshot::new(*client_id, account.available, account.held, account.locked)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::tx_id;
+    use crate::common::types::TransactionType::{
+        Chargeback, Deposit, Dispute, Resolve, Withdrawal,
+    };
+    use rust_decimal_macros::dec;
+    use Transaction as Tx;
+
+    #[test]
+    fn test_reference_rules() {
+        let mut ledger = ReferenceLedger::new();
+
+        assert!(ledger.apply(&Tx::new(tx_id(1), Deposit, 1, Some(dec!(10.0)))));
+        assert!(ledger.apply(&Tx::new(tx_id(2), Withdrawal, 1, Some(dec!(4.0)))));
+        assert!(!ledger.apply(&Tx::new(tx_id(3), Withdrawal, 2, Some(dec!(1.0)))));
+        assert!(!ledger.apply(&Tx::new(tx_id(1), Deposit, 1, Some(dec!(1.0)))));
+        assert!(!ledger.apply(&Tx::new(tx_id(1), Dispute, 2, None)));
+
+        assert!(ledger.apply(&Tx::new(tx_id(1), Dispute, 1, None)));
+        assert!(!ledger.apply(&Tx::new(tx_id(1), Dispute, 1, None)));
+        assert!(ledger.apply(&Tx::new(tx_id(1), Resolve, 1, None)));
+        assert!(!ledger.apply(&Tx::new(tx_id(1), Chargeback, 1, None)));
+        assert!(ledger.apply(&Tx::new(tx_id(2), Dispute, 1, None)));
+        assert!(ledger.apply(&Tx::new(tx_id(2), Chargeback, 1, None)));
+        assert!(!ledger.apply(&Tx::new(tx_id(4), Deposit, 1, Some(dec!(1.0)))));
+
+        let accounts = ledger.get_accounts();
+        assert_eq!(
+            accounts,
+            vec![
+                ClientSnapshot::new(1, dec!(10.0), dec!(0), true),
+                ClientSnapshot::new(2, dec!(0), dec!(0), false),
+            ]
+        );
+    }
+}
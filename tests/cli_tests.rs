@@ -0,0 +1,238 @@
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+    use std::process::{Command, Output};
+
+    fn run_cli(args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_toy_payments_engine"))
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    // the accounts are written in no particular order
+    fn sorted_lines(output: &[u8]) -> Vec<String> {
+        let mut lines: Vec<String> = String::from_utf8_lossy(output)
+            .lines()
+            .map(String::from)
+            .collect();
+        lines.sort();
+        lines
+    }
+
+    fn input(name: &str) -> String {
+        format!("{}/tests/inputs/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[rstest]
+    #[case(vec![], "client,available,held,total,locked")]
+    #[case(vec!["-vv", "--log-format", "json"], "client,available,held,total,locked")]
+    #[case(vec!["--verify", "--rejections", "skip"], "client,available,held,total,locked")]
+    #[case(vec!["trial-balance"], "account,debit,credit")]
+    #[case(vec!["validate"], "5 rows: 0 invalid, 4 accepted, 1 rejected")]
+    #[case(vec!["validate", "--store", "disk"], "5 rows: 0 invalid, 4 accepted, 1 rejected")]
+    fn test_subcommand_runs(#[case] args: Vec<&str>, #[case] expected: &str) {
+        // Arrange
+        let simple = input("simple.csv");
+        let mut args = args;
+        args.push(&simple);
+
+        // Act
+        let output = run_cli(&args);
+
+        // Assert
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(stdout.contains(expected), "{}", stdout);
+    }
+
+    #[test]
+    fn test_metrics_file() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = dir.path().join("ledger.prom");
+
+        // Act
+        let output = run_cli(&["--metrics", metrics.to_str().unwrap(), &input("simple.csv")]);
+
+        // Assert
+        assert!(output.status.success());
+        let metrics = std::fs::read_to_string(metrics).unwrap();
+        assert!(metrics.contains("ledger_rows_total 5"));
+    }
+
+    #[test]
+    fn test_convert_and_run_binary() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("simple.txbin");
+
+        // Act
+        let convert = run_cli(&[
+            "convert",
+            "--output",
+            binary.to_str().unwrap(),
+            &input("simple.csv"),
+        ]);
+        let run = run_cli(&[binary.to_str().unwrap()]);
+
+        // Assert
+        assert!(convert.status.success());
+        let expected = run_cli(&[&input("simple.csv")]);
+        assert_eq!(sorted_lines(&run.stdout), sorted_lines(&expected.stdout));
+    }
+
+    #[test]
+    fn test_generate() {
+        // Act
+        let output = run_cli(&["generate", "--seed", "1", "--rows", "10"]);
+
+        // Assert
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.lines().count(), 11);
+    }
+
+    #[test]
+    fn test_validate_does_not_persist() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("ledger.db");
+        let store = store.to_str().unwrap();
+
+        // Act
+        let output = run_cli(&[
+            "validate",
+            "--store",
+            "sqlite",
+            "--store-path",
+            store,
+            &input("simple.csv"),
+        ]);
+
+        // Assert
+        assert!(!output.status.success());
+        assert!(!std::path::Path::new(store).exists());
+    }
+
+    #[test]
+    fn test_trial_balance_total_overflow_is_reported() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("large.csv");
+        let max = "79228162514264337593543950335";
+        std::fs::write(
+            &input,
+            format!(
+                "type,client,tx,amount\ndeposit,1,1,{max}\nwithdrawal,1,2,{max}\n\
+                 dispute,1,2,\ndeposit,2,3,{max}\n"
+            ),
+        )
+        .unwrap();
+
+        // Act
+        let output = run_cli(&["trial-balance", input.to_str().unwrap()]);
+
+        // Assert
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("TotalOverflow"), "{}", stderr);
+    }
+
+    #[test]
+    fn test_disk_store_refuses_earlier_history() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("history.redb");
+        let args = [
+            "--store",
+            "disk",
+            "--store-path",
+            store.to_str().unwrap(),
+            &input("simple.csv"),
+        ];
+
+        // Act
+        let first = run_cli(&args);
+        let second = run_cli(&args);
+
+        // Assert
+        assert!(first.status.success());
+        assert_eq!(second.status.code(), Some(1));
+        assert!(second.stdout.is_empty());
+        let stderr = String::from_utf8_lossy(&second.stderr);
+        assert!(
+            stderr.contains("already holds a transaction history"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_as_of_transaction_skips_invalid_rows() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("malformed.csv");
+        std::fs::write(
+            &input,
+            "type,client,tx,amount\ndeposit,1,1,10.0\ntransfer,1,2,3.0\n\
+             deposit,1,3,5.0\ndeposit,1,4,1.0\n",
+        )
+        .unwrap();
+
+        // Act
+        let output = run_cli(&[
+            "--invalid-rows",
+            "skip",
+            "--as-of-transaction",
+            "2",
+            input.to_str().unwrap(),
+        ]);
+
+        // Assert
+        assert!(output.status.success());
+        assert_eq!(
+            sorted_lines(&output.stdout),
+            vec!["1,15.0,0,15.0,false", "client,available,held,total,locked"]
+        );
+    }
+
+    #[test]
+    fn test_store_path_requires_persistent_store() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("ledger.db");
+
+        // Act
+        let output = run_cli(&[
+            "--store-path",
+            store.to_str().unwrap(),
+            &input("simple.csv"),
+        ]);
+
+        // Assert
+        assert_eq!(output.status.code(), Some(1));
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("--store-path requires"), "{}", stderr);
+    }
+
+    #[test]
+    fn test_dispute_window_overflow_is_reported() {
+        // Act
+        let output = run_cli(&[
+            "--dispute-window-days",
+            &u64::MAX.to_string(),
+            &input("simple.csv"),
+        ]);
+
+        // Assert
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("--dispute-window-days"), "{}", stderr);
+    }
+}
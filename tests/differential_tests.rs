@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+    use toy_payments_engine::models::transaction::Transaction;
+    use toy_payments_engine::support::differential::first_divergence;
+    use toy_payments_engine::support::generator::{GeneratorConfig, TransactionGenerator};
+    use toy_payments_engine::{DiskTransactionStore, MemoryAccountStore, TransactionManager};
+
+    fn read_transactions(name: &str) -> Vec<Transaction> {
+        let path = format!("{}/tests/inputs/{}.csv", env!("CARGO_MANIFEST_DIR"), name);
+        let mut reader = csv::Reader::from_path(path).unwrap();
+        reader
+            .records()
+            .map(|record| Transaction::try_from(record.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[rstest]
+    #[case("simple")]
+    #[case("big")]
+    #[case("full")]
+    fn test_fixture_matches_reference(#[case] name: &str) {
+        // Arrange
+        let transactions = read_transactions(name);
+
+        // Act
+        let divergence = first_divergence(TransactionManager::new(), transactions).unwrap();
+
+        // Assert
+        if let Some(divergence) = divergence {
+            panic!("{}", divergence);
+        }
+    }
+
+    #[rstest]
+    #[case(GeneratorConfig::default())]
+    #[case(GeneratorConfig {
+        seed: 2,
+        clients: 5,
+        dispute_rate: 0.2,
+        ..GeneratorConfig::default()
+    })]
+    #[case(GeneratorConfig {
+        seed: 3,
+        clients: 1000,
+        duplicate_rate: 0.1,
+        rejection_rate: 0.1,
+        ..GeneratorConfig::default()
+    })]
+    #[case(GeneratorConfig {
+        seed: 4,
+        chargeback_rate: 1.0,
+        dispute_rate: 0.3,
+        ..GeneratorConfig::default()
+    })]
+    fn test_generated_stream_matches_reference(#[case] config: GeneratorConfig) {
+        // Arrange
+        let transactions = TransactionGenerator::new(config).unwrap();
+
+        // Act
+        let divergence = first_divergence(TransactionManager::new(), transactions).unwrap();
+
+        // Assert
+        if let Some(divergence) = divergence {
+            panic!("{}", divergence);
+        }
+    }
+
+    #[test]
+    fn test_disk_history_matches_reference() {
+        // Arrange
+        let config = GeneratorConfig {
+            dispute_rate: 0.1,
+            ..GeneratorConfig::default()
+        };
+        let transactions = TransactionGenerator::new(config).unwrap();
+        let tx_history = DiskTransactionStore::temporary(64).unwrap();
+        let manager = TransactionManager::with_stores(MemoryAccountStore::new(), tx_history);
+
+        // Act
+        let divergence = first_divergence(manager, transactions).unwrap();
+
+        // Assert
+        if let Some(divergence) = divergence {
+            panic!("{}", divergence);
+        }
+    }
+}
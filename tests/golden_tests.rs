@@ -0,0 +1,296 @@
+mod helpers;
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use toy_payments_engine::common::compression::{CompressedWriter, Compression};
+    use toy_payments_engine::models::rejection::Rejection;
+    use toy_payments_engine::{
+        convert_files_to_binary, run_transactions_from_file,
+        run_transactions_from_file_with_options, write_rejections, RunOptions,
+    };
+
+    const INPUT_EXTENSIONS: [&str; 2] = ["csv", "jsonl"];
+
+    // set to regenerate the expected files instead of comparing against them
+    const BLESS_VAR: &str = "BLESS";
+
+    fn is_bless_mode() -> bool {
+        std::env::var_os(BLESS_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+    }
+
+    fn render_rejections(rejections: &[Rejection]) -> String {
+        let mut output = Vec::new();
+        write_rejections(&mut output, rejections).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Compares `actual` with the expected file, or overwrites the file in bless mode.
+    fn check_golden(expected_file: &Path, actual: &str) -> Result<(), String> {
+        if is_bless_mode() {
+            if let Some(dir) = expected_file.parent() {
+                fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            }
+            return fs::write(expected_file, actual).map_err(|err| err.to_string());
+        }
+
+        let expected = match fs::read_to_string(expected_file) {
+            Ok(expected) => expected,
+            Err(err) => {
+                return Err(format!(
+                    "cannot read {}: {}; run with {}=1 to create it",
+                    expected_file.display(),
+                    err,
+                    BLESS_VAR
+                ))
+            }
+        };
+        match expected == actual {
+            true => Ok(()),
+            false => Err(format!(
+                "{} differs\n--- expected\n{}--- actual\n{}",
+                expected_file.display(),
+                expected,
+                actual
+            )),
+        }
+    }
+
+    // every `inputs/<name>.csv` or `inputs/<name>.jsonl` is expected to have an
+    // `outputs/<name>.csv` with the accounts and a `rejects/<name>.csv` with the rejected rows
+    fn discover_fixtures() -> Vec<String> {
+        let inputs = crate::helpers::get_test_file_path("inputs");
+        let mut names: Vec<String> = fs::read_dir(inputs)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| INPUT_EXTENSIONS.iter().any(|ext| extension == *ext))
+            })
+            .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn golden_file(dir: &str, name: &str) -> PathBuf {
+        crate::helpers::get_test_file_path(&format!("{}/{}.csv", dir, name))
+    }
+
+    fn input_file(name: &str) -> PathBuf {
+        INPUT_EXTENSIONS
+            .iter()
+            .map(|extension| {
+                crate::helpers::get_test_file_path(&format!("inputs/{}.{}", name, extension))
+            })
+            .find(|path| path.exists())
+            .unwrap()
+    }
+
+    fn check_fixture(name: &str) -> Vec<String> {
+        let input_file = input_file(name);
+        let options = RunOptions {
+            record_rejections: true,
+            ..RunOptions::default()
+        };
+        let report = match run_transactions_from_file_with_options(input_file, &options) {
+            Ok(report) => report,
+            Err(err) => return vec![format!("{}: {}", name, err)],
+        };
+
+        let accounts = crate::helpers::render_accounts(report.clients);
+        let rejections = render_rejections(&report.rejections);
+        [
+            check_golden(&golden_file("outputs", name), &accounts),
+            check_golden(&golden_file("rejects", name), &rejections),
+        ]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect()
+    }
+
+    #[test]
+    fn test_golden_files() {
+        // Arrange
+        let names = discover_fixtures();
+
+        // Act
+        let failures: Vec<String> = names.iter().flat_map(|name| check_fixture(name)).collect();
+
+        // Assert
+        assert!(names.contains(&"rejects".to_string()));
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_every_expected_file_has_an_input() {
+        // Arrange
+        let names = discover_fixtures();
+
+        for dir in ["outputs", "rejects"] {
+            // Act
+            let orphans: Vec<PathBuf> = fs::read_dir(crate::helpers::get_test_file_path(dir))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| {
+                    let name = path.file_stem().unwrap().to_string_lossy();
+                    !names.iter().any(|input| *input == name)
+                })
+                .collect();
+
+            // Assert
+            assert!(orphans.is_empty(), "no input for {:?}", orphans);
+        }
+    }
+
+    // compressed copies of every fixture have to give the same accounts, whether the
+    // compression is told by the extension or only by the magic number
+    #[rstest]
+    fn test_compressed_inputs(
+        #[values(Compression::Gzip, Compression::Zstd)] compression: Compression,
+        #[values("", ".gz", ".zst")] suffix: &str,
+    ) {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let names = discover_fixtures();
+
+        for name in names.iter() {
+            let input = input_file(name);
+            let content = fs::read(&input).unwrap();
+            // the format is still told by the extension before the compression suffix
+            let file_name = input.file_name().unwrap().to_string_lossy();
+            let input_file = dir.path().join(format!("{}{}", file_name, suffix));
+            let mut writer =
+                CompressedWriter::new(File::create(&input_file).unwrap(), compression).unwrap();
+            writer.write_all(&content).unwrap();
+            writer.finish().unwrap();
+
+            // Act
+            let clients = run_transactions_from_file(&input_file).unwrap();
+
+            // Assert
+            let expected = crate::helpers::read_golden(&golden_file("outputs", name));
+            assert_eq!(
+                crate::helpers::render_accounts(clients),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    // every fixture converted to the binary format has to give the same accounts and rejections
+    #[test]
+    fn test_binary_inputs() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let names = discover_fixtures();
+
+        for name in names.iter() {
+            let input_file = dir.path().join(format!("{}.txbin", name));
+            let writer = File::create(&input_file).unwrap();
+            let converted =
+                convert_files_to_binary(&[self::input_file(name)], &RunOptions::default(), writer)
+                    .unwrap();
+
+            // Act
+            let report =
+                run_transactions_from_file_with_options(&input_file, &RunOptions::default())
+                    .unwrap();
+
+            // Assert
+            assert_eq!(converted.rows, report.rows, "{}", name);
+            assert_eq!(
+                report.get_rejected(),
+                crate::helpers::read_golden(&golden_file("rejects", name))
+                    .lines()
+                    .count() as u64
+                    - 1,
+                "{}",
+                name
+            );
+            assert_eq!(
+                crate::helpers::render_accounts(report.clients),
+                crate::helpers::read_golden(&golden_file("outputs", name)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    // the CSV fixtures as Parquet and Arrow IPC files, every column as strings, have to give the
+    // same accounts and rejections
+    #[cfg(feature = "parquet")]
+    #[rstest]
+    fn test_columnar_inputs(#[values("parquet", "arrow")] extension: &str) {
+        use arrow_array::{ArrayRef, RecordBatch, StringArray};
+        use std::sync::Arc;
+
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let names = discover_fixtures();
+
+        for name in names
+            .iter()
+            .filter(|name| input_file(name).ends_with(format!("{}.csv", name)))
+        {
+            let mut reader = csv::Reader::from_path(input_file(name)).unwrap();
+            let header = reader.headers().unwrap().clone();
+            let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+            let columns: Vec<(String, ArrayRef)> = header
+                .iter()
+                .enumerate()
+                .map(|(index, field)| {
+                    let values: Vec<&str> = records
+                        .iter()
+                        .map(|record| record.get(index).unwrap_or_default())
+                        .collect();
+                    let array: ArrayRef = Arc::new(StringArray::from(values));
+                    (field.to_string(), array)
+                })
+                .collect();
+            let input_file = dir.path().join(format!("{}.{}", name, extension));
+            let file = File::create(&input_file).unwrap();
+            let batch = RecordBatch::try_from_iter(columns).unwrap();
+            match extension {
+                "parquet" => {
+                    let mut writer =
+                        parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+                    writer.write(&batch).unwrap();
+                    writer.close().unwrap();
+                }
+                _ => {
+                    let mut writer =
+                        arrow_ipc::writer::FileWriter::try_new(file, &batch.schema()).unwrap();
+                    writer.write(&batch).unwrap();
+                    writer.finish().unwrap();
+                }
+            }
+
+            // Act
+            let report =
+                run_transactions_from_file_with_options(&input_file, &RunOptions::default())
+                    .unwrap();
+
+            // Assert
+            assert_eq!(
+                report.get_rejected(),
+                crate::helpers::read_golden(&golden_file("rejects", name))
+                    .lines()
+                    .count() as u64
+                    - 1,
+                "{}",
+                name
+            );
+            assert_eq!(
+                crate::helpers::render_accounts(report.clients),
+                crate::helpers::read_golden(&golden_file("outputs", name)),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use toy_payments_engine::models::client_snapshot::ClientSnapshot;
+use toy_payments_engine::write_accounts;
+
+#[cfg(test)]
+pub(crate) fn get_test_file_path(path: &str) -> PathBuf {
+    // this file is tests/helpers/mod.rs
+    let mut test_file_path = PathBuf::from(file!());
+    test_file_path.pop();
+    test_file_path.pop();
+    test_file_path.push(path);
+    test_file_path
+}
+
+/// The accounts the way the CLI prints them, ordered by client id.
+#[cfg(test)]
+pub(crate) fn render_accounts(mut clients: Vec<ClientSnapshot>) -> String {
+    clients.sort_by_key(|client| client.get_id());
+    let mut output = Vec::new();
+    write_accounts(&mut output, &clients).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+/// The expected file as it is, without blessing it.
+#[cfg(test)]
+pub(crate) fn read_golden(expected_file: &Path) -> String {
+    fs::read_to_string(expected_file).unwrap()
+}
@@ -3,24 +3,505 @@ mod helpers;
 #[cfg(test)]
 mod test {
     use rstest::rstest;
-    use toy_payments_engine::run_transactions_from_file;
+    use std::fs::File;
+    use toy_payments_engine::support::generator::{
+        GeneratedRow, GeneratorConfig, TransactionGenerator,
+    };
+    use toy_payments_engine::support::reference::ReferenceLedger;
+    use toy_payments_engine::{
+        replay, run_transactions_from_file, run_transactions_from_file_with_options,
+        run_transactions_from_files, write_rows, write_transactions, AppError, ErrorPolicy,
+        RunOptions, StoreKind,
+    };
+
+    #[rstest]
+    fn test_use_case_with_store(
+        #[values("simple", "big", "full")] name: &str,
+        #[values("disk", "sqlite")] store: &str,
+    ) {
+        // Arrange
+        let input_file = crate::helpers::get_test_file_path(&format!("inputs/{}.csv", name));
+        let output_file = crate::helpers::get_test_file_path(&format!("outputs/{}.csv", name));
+        let store_dir = tempfile::tempdir().unwrap();
+        let store_path = store_dir.path().join(format!("{}.db", name));
+        let options = RunOptions {
+            store: match store {
+                "disk" => StoreKind::Disk(Some(store_path)),
+                _ => StoreKind::Sqlite(store_path),
+            },
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(input_file, &options).unwrap();
+
+        // Assert
+        let expected = crate::helpers::read_golden(&output_file);
+        assert_eq!(crate::helpers::render_accounts(report.clients), expected);
+    }
 
     #[rstest]
-    #[case("empty")]
     #[case("simple")]
     #[case("big")]
     #[case("full")]
-    fn test_use_case(#[case] name: &str) {
+    fn test_replayed_events_match_output(#[case] name: &str) {
         // Arrange
         let input_file = crate::helpers::get_test_file_path(&format!("inputs/{}.csv", name));
         let output_file = crate::helpers::get_test_file_path(&format!("outputs/{}.csv", name));
-        let output_lines_raw = crate::helpers::read_csv(&output_file).unwrap();
-        let mut output_lines = crate::helpers::deserialize_output_lines(output_lines_raw);
+        let options = RunOptions {
+            record_events: true,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(input_file, &options).unwrap();
+        let clients = replay(&report.events).unwrap();
+
+        // Assert
+        let expected = crate::helpers::read_golden(&output_file);
+        assert_eq!(crate::helpers::render_accounts(clients), expected);
+    }
+
+    #[rstest]
+    #[case("simple")]
+    #[case("big")]
+    #[case("full")]
+    fn test_books_balance(#[case] name: &str) {
+        // Arrange
+        let input_file = crate::helpers::get_test_file_path(&format!("inputs/{}.csv", name));
+        let options = RunOptions {
+            bookkeeping: true,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(input_file, &options).unwrap();
+
+        // Assert
+        assert_eq!(report.trial_balance.unwrap().is_balanced(), Ok(true));
+        assert_eq!(report.reconciliation, vec![]);
+    }
+
+    #[rstest]
+    fn test_audit_finds_no_discrepancies(
+        #[values("simple", "big", "full")] name: &str,
+        #[values("memory", "disk", "sqlite")] store: &str,
+    ) {
+        // Arrange
+        let input_file = crate::helpers::get_test_file_path(&format!("inputs/{}.csv", name));
+        let store_dir = tempfile::tempdir().unwrap();
+        let store_path = store_dir.path().join(format!("{}.db", name));
+        let options = RunOptions {
+            store: match store {
+                "memory" => StoreKind::Memory,
+                "disk" => StoreKind::Disk(Some(store_path)),
+                _ => StoreKind::Sqlite(store_path),
+            },
+            verify: true,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(input_file, &options).unwrap();
+
+        // Assert
+        assert_eq!(report.audit, vec![]);
+    }
+
+    #[rstest]
+    fn test_generated_stream_matches_reference(#[values(1, 2, 3)] seed: u64) {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input_file = dir.path().join("generated.csv");
+        let config = GeneratorConfig {
+            seed,
+            clients: 20,
+            rows: 5000,
+            dispute_rate: 0.05,
+            ..GeneratorConfig::default()
+        };
+        let mut reference = ReferenceLedger::new();
+        let transactions = TransactionGenerator::new(config).unwrap().inspect(|tx| {
+            reference.apply(tx);
+        });
+        write_transactions(File::create(&input_file).unwrap(), transactions).unwrap();
+
+        // Act
+        let mut clients = run_transactions_from_file(&input_file).unwrap();
+        clients.sort_by_key(|client| client.get_id());
+
+        // Assert
+        assert_eq!(clients, reference.get_accounts());
+    }
+
+    #[test]
+    fn test_generated_malformed_rows_are_invalid() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input_file = dir.path().join("generated.csv");
+        let config = GeneratorConfig {
+            clients: 20,
+            rows: 5000,
+            malformed_rate: 0.05,
+            ..GeneratorConfig::default()
+        };
+        let mut reference = ReferenceLedger::new();
+        let mut malformed = 0;
+        let rows = TransactionGenerator::new(config)
+            .unwrap()
+            .rows()
+            .inspect(|row| match row {
+                GeneratedRow::Transaction(tx) => {
+                    reference.apply(tx);
+                }
+                GeneratedRow::Malformed(_) => malformed += 1,
+            });
+        write_rows(File::create(&input_file).unwrap(), rows).unwrap();
+        let options = RunOptions {
+            invalid_rows: ErrorPolicy::Skip,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(&input_file, &options).unwrap();
+
+        // Assert
+        let mut clients = report.clients;
+        clients.sort_by_key(|client| client.get_id());
+        assert_eq!(clients, reference.get_accounts());
+        assert!(malformed > 0);
+        assert_eq!(report.invalid_rows.len(), malformed);
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input_file = dir.path().join("invalid.csv");
+        let input = "type, client, tx, amount\n\
+                     deposit, 1, 1, 1.0\n\
+                     deposit, x, 2, 1.0\n\
+                     withdrawal, 1, 3, 5.0\n\
+                     refund, 1, 4, 1.0\n\
+                     deposit, 1, 5\n\
+                     dispute, 1, 9,\n";
+        std::fs::write(&input_file, input).unwrap();
+        let options = RunOptions {
+            invalid_rows: ErrorPolicy::Skip,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let strict = run_transactions_from_file(&input_file);
+        let report = run_transactions_from_file_with_options(&input_file, &options).unwrap();
+
+        // Assert
+        assert!(strict.is_err());
+        assert_eq!(report.rows, 6);
+        let lines: Vec<u64> = report
+            .invalid_rows
+            .iter()
+            .map(|row| row.get_line())
+            .collect();
+        assert_eq!(lines, vec![3, 5, 6]);
+        let by_kind: Vec<(&str, u64)> = report.rejection_counts.into_iter().collect();
+        assert_eq!(
+            by_kind,
+            vec![("insufficient_funds", 1), ("transaction_not_found", 1)]
+        );
+    }
+
+    #[rstest]
+    #[case(ErrorPolicy::Abort, Some(6))]
+    #[case(ErrorPolicy::AbortAfter(2), Some(14))]
+    #[case(ErrorPolicy::AbortAfter(5), None)]
+    #[case(ErrorPolicy::Skip, None)]
+    fn test_rejection_policy(#[case] policy: ErrorPolicy, #[case] stopped_at: Option<u64>) {
+        // Arrange
+        let input_file = crate::helpers::get_test_file_path("inputs/full.csv");
+        let options = RunOptions {
+            rejections: policy,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let result = run_transactions_from_file_with_options(input_file, &options);
+
+        // Assert
+        match (result, stopped_at) {
+            (Ok(report), None) => assert_eq!(report.get_rejected(), 4),
+            (Err(AppError::Rejected(rejection)), Some(line)) => {
+                assert_eq!(rejection.get_line(), line)
+            }
+            (Err(AppError::TooManyErrors(errors, err)), Some(line)) => {
+                assert_eq!(errors, 2);
+                assert!(
+                    matches!(*err, AppError::Rejected(rejection) if rejection.get_line() == line)
+                );
+            }
+            (result, _) => panic!("unexpected outcome {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_invalid_rows_abort_after_limit() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input_file = dir.path().join("invalid.csv");
+        let input = "type, client, tx, amount\n\
+                     deposit, x, 1, 1.0\n\
+                     deposit, 1, 2, 1.0\n\
+                     deposit, y, 3, 1.0\n";
+        std::fs::write(&input_file, input).unwrap();
+        let lenient = RunOptions {
+            invalid_rows: ErrorPolicy::AbortAfter(3),
+            ..RunOptions::default()
+        };
+        let strict = RunOptions {
+            invalid_rows: ErrorPolicy::AbortAfter(2),
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(&input_file, &lenient).unwrap();
+        let result = run_transactions_from_file_with_options(&input_file, &strict);
+
+        // Assert
+        assert_eq!(report.invalid_rows.len(), 2);
+        assert_eq!(report.clients.len(), 1);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "stopped after 2 errors, the last one being invalid row on line 4: \
+             Transaction error: Invalid client id 'y': invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_delimiter_and_metadata() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input_file = dir.path().join("semicolons.csv");
+        let input = "Tx;Client;Type;Amount;Channel\n\
+                     1;1;deposit;1.0;web\n\
+                     2;1;withdrawal;5.0;branch\n";
+        std::fs::write(&input_file, input).unwrap();
+        let options = RunOptions {
+            delimiter: b';',
+            record_rejections: true,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_file_with_options(&input_file, &options).unwrap();
+
+        // Assert
+        assert_eq!(report.clients.len(), 1);
+        let rejected = report.rejections[0].get_transaction();
+        assert_eq!(
+            rejected.get_metadata(),
+            &[("Channel".to_string(), "branch".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_multiple_files_share_the_ledger() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let monday = dir.path().join("monday.csv");
+        let tuesday = dir.path().join("tuesday.csv");
+        std::fs::write(
+            &monday,
+            "type,client,tx,amount\n\
+             deposit,1,1,10.0\n\
+             withdrawal,1,2,20.0\n",
+        )
+        .unwrap();
+        // a later file may order its columns differently
+        std::fs::write(
+            &tuesday,
+            "client,type,tx,amount\n\
+             x,deposit,3,1.0\n\
+             1,dispute,1,\n\
+             1,deposit,1,5.0\n",
+        )
+        .unwrap();
+        let options = RunOptions {
+            invalid_rows: ErrorPolicy::Skip,
+            record_rejections: true,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let report = run_transactions_from_files(&[&monday, &tuesday], &options).unwrap();
+
+        // Assert
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.clients.len(), 1);
+        assert_eq!(report.clients[0].get_held().to_string(), "10.0");
+        let invalid = report.invalid_rows[0].get_location();
+        assert_eq!(
+            (invalid.get_file(), invalid.get_line()),
+            (Some(tuesday.as_path()), 2)
+        );
+        let rejected: Vec<_> = report
+            .rejections
+            .iter()
+            .map(|rejection| {
+                let location = rejection.get_location();
+                (location.get_file().unwrap(), location.get_line())
+            })
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![(monday.as_path(), 3), (tuesday.as_path(), 4)]
+        );
+    }
+
+    #[rstest]
+    #[case("simple")]
+    #[case("full")]
+    fn test_metrics_match_report(#[case] name: &str) {
+        // Arrange
+        let input_file = crate::helpers::get_test_file_path(&format!("inputs/{}.csv", name));
+        let options = RunOptions {
+            metrics: true,
+            ..RunOptions::default()
+        };
 
         // Act
-        let clients = run_transactions_from_file(input_file).unwrap();
+        let report = run_transactions_from_file_with_options(input_file, &options).unwrap();
 
         // Assert
-        crate::helpers::compare_expected_output_with_actual(&mut output_lines, clients);
+        let metrics = report.metrics.as_ref().unwrap();
+        let locked = report
+            .clients
+            .iter()
+            .filter(|client| client.get_locked())
+            .count();
+        assert_eq!(metrics.get_rows(), report.rows);
+        assert_eq!(metrics.get_process_latency().get_count(), report.rows);
+        assert_eq!(metrics.get_locked_accounts(), locked as u64);
+        for (kind, count) in &report.rejection_counts {
+            assert_eq!(metrics.get_rejections(kind), *count);
+        }
+    }
+
+    #[test]
+    fn test_open_disputes_span_runs() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let monday = dir.path().join("monday.csv");
+        let tuesday = dir.path().join("tuesday.csv");
+        std::fs::write(
+            &monday,
+            "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,3.0\ndispute,1,1,\n",
+        )
+        .unwrap();
+        std::fs::write(&tuesday, "type,client,tx,amount\nresolve,1,1,\n").unwrap();
+        let options = RunOptions {
+            metrics: true,
+            store: StoreKind::Sqlite(dir.path().join("ledger.db")),
+            ..RunOptions::default()
+        };
+
+        // Act
+        let first = run_transactions_from_file_with_options(&monday, &options).unwrap();
+        let second = run_transactions_from_file_with_options(&tuesday, &options).unwrap();
+
+        // Assert
+        assert_eq!(first.metrics.unwrap().get_open_disputes(), 1);
+        assert_eq!(second.metrics.unwrap().get_open_disputes(), 0);
+    }
+
+    #[rstest]
+    fn test_aborted_run_is_not_committed(#[values("disk", "sqlite")] store: &str) {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let input_file = crate::helpers::get_test_file_path("inputs/full.csv");
+        let empty_file = crate::helpers::get_test_file_path("inputs/empty.csv");
+        let store_path = dir.path().join("ledger.db");
+        let store = match store {
+            "disk" => StoreKind::Disk(Some(store_path)),
+            _ => StoreKind::Sqlite(store_path),
+        };
+        let aborting = RunOptions {
+            rejections: ErrorPolicy::Abort,
+            store: store.clone(),
+            ..RunOptions::default()
+        };
+        let options = RunOptions {
+            store,
+            ..RunOptions::default()
+        };
+
+        // Act
+        let aborted = run_transactions_from_file_with_options(input_file, &aborting);
+        let report = run_transactions_from_file_with_options(empty_file, &options).unwrap();
+
+        // Assert
+        assert!(matches!(aborted, Err(AppError::Rejected(_))));
+        assert_eq!(report.clients.len(), 0);
+    }
+
+    #[test]
+    fn test_log_carries_client_trail() {
+        // Arrange
+        let input_file = crate::helpers::get_test_file_path("inputs/simple.csv");
+        let log = SharedLog::default();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(log.clone())
+            .finish();
+
+        // Act
+        let result = tracing::subscriber::with_default(subscriber, || {
+            run_transactions_from_file(input_file)
+        });
+
+        // Assert
+        assert!(result.is_ok());
+        // every line logged for client 2, as its transaction id and outcome
+        let trail: Vec<String> = log
+            .lines()
+            .iter()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|entry| entry["span"]["client"] == "2")
+            .map(|entry| format!("{} {}", entry["span"]["tx"], entry["span"]["outcome"]))
+            .collect();
+        let expected = vec!["\"2\" \"accepted\"", "\"5\" \"insufficient_funds\""];
+        assert_eq!(trail, expected);
+    }
+
+    /// A log writer for tests, shared between the subscriber and the test reading it back.
+    #[derive(Clone, Default)]
+    struct SharedLog(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl SharedLog {
+        fn lines(&self) -> Vec<String> {
+            let log = self.0.lock().unwrap();
+            String::from_utf8_lossy(&log)
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    impl std::io::Write for SharedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for SharedLog {
+        type Writer = SharedLog;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
     }
 }
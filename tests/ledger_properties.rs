@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod test {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use rust_decimal::Decimal;
+    use std::collections::{HashMap, HashSet};
+    use std::ops::RangeInclusive;
+    use toy_payments_engine::common::types::{ClientId, TransactionId, TransactionType};
+    use toy_payments_engine::models::client_snapshot::ClientSnapshot;
+    use toy_payments_engine::models::transaction::Transaction;
+    use toy_payments_engine::{AuditFinding, ProcessingError, TransactionManager};
+
+    const CLIENTS: RangeInclusive<u16> = 1..=3;
+    const TX_IDS: RangeInclusive<u32> = 1..=20;
+
+    // parsed so the tests work with every id width
+    fn tx_id(id: u32) -> TransactionId {
+        id.to_string().parse().unwrap()
+    }
+
+    fn client_id(id: u16) -> ClientId {
+        ClientId::from(id)
+    }
+
+    fn amount() -> impl Strategy<Value = Decimal> {
+        (1i64..=1_000_000).prop_map(|units| Decimal::new(units, 4))
+    }
+
+    // small id ranges so that disputes, duplicates and locked accounts come up often
+    fn transaction(
+        clients: RangeInclusive<u16>,
+        tx_ids: RangeInclusive<u32>,
+    ) -> impl Strategy<Value = Transaction> {
+        (0..8u8, clients, tx_ids, amount()).prop_map(|(kind, client, tx, amount)| {
+            let (tx_type, amount) = match kind {
+                0..=2 => (TransactionType::Deposit, Some(amount)),
+                3..=4 => (TransactionType::Withdrawal, Some(amount)),
+                5 => (TransactionType::Dispute, None),
+                6 => (TransactionType::Resolve, None),
+                _ => (TransactionType::Chargeback, None),
+            };
+            Transaction::new(tx_id(tx), tx_type, client_id(client), amount)
+        })
+    }
+
+    fn stream() -> impl Strategy<Value = Vec<Transaction>> {
+        vec(transaction(CLIENTS, TX_IDS), 0..200)
+    }
+
+    // every client gets its own range of transaction ids
+    fn client_stream(client: u16) -> impl Strategy<Value = Vec<Transaction>> {
+        let first = u32::from(client) * 100;
+        vec(transaction(client..=client, first + 1..=first + 20), 0..60)
+    }
+
+    fn snapshot(manager: &TransactionManager, client: ClientId) -> ClientSnapshot {
+        manager
+            .get_snapshot(client)
+            .unwrap()
+            .unwrap_or_else(|| ClientSnapshot::new(client, Decimal::ZERO, Decimal::ZERO, false))
+    }
+
+    fn sorted_snapshots(manager: TransactionManager) -> Vec<ClientSnapshot> {
+        let mut snapshots = manager.get_all_values().unwrap();
+        snapshots.sort_by_key(|snapshot| snapshot.get_id());
+        snapshots
+    }
+
+    proptest! {
+        #[test]
+        fn test_held_is_never_negative(transactions in stream()) {
+            let mut manager = TransactionManager::new();
+
+            for tx in transactions {
+                let client = tx.get_client_id();
+                let _ = manager.add_transaction(tx);
+                prop_assert!(snapshot(&manager, client).get_held() >= Decimal::ZERO);
+            }
+            prop_assert_eq!(manager.audit().unwrap(), Vec::<AuditFinding>::new());
+        }
+
+        #[test]
+        fn test_rejected_transactions_leave_the_account_unchanged(transactions in stream()) {
+            let mut manager = TransactionManager::new();
+
+            for tx in transactions {
+                let client = tx.get_client_id();
+                let before = snapshot(&manager, client);
+                if manager.add_transaction(tx).is_err() {
+                    prop_assert_eq!(snapshot(&manager, client), before);
+                }
+            }
+        }
+
+        #[test]
+        fn test_dispute_then_resolve_conserves_money(
+            transactions in stream(),
+            disputed in TX_IDS,
+        ) {
+            let mut manager = TransactionManager::new();
+            let mut accepted = HashMap::new();
+            for tx in transactions {
+                let (id, tx_type) = (tx.get_transaction_id(), tx.get_transaction_type());
+                if manager.add_transaction(tx).is_ok() {
+                    accepted.entry(id).or_insert(tx_type);
+                }
+            }
+
+            for client in CLIENTS.map(client_id) {
+                let before = snapshot(&manager, client);
+                let dispute = Transaction::new(tx_id(disputed), TransactionType::Dispute, client, None);
+                if manager.add_transaction(dispute).is_err() {
+                    continue;
+                }
+                if accepted.get(&tx_id(disputed)) == Some(&TransactionType::Deposit) {
+                    prop_assert_eq!(snapshot(&manager, client).get_total(), before.get_total());
+                }
+
+                let resolve = Transaction::new(tx_id(disputed), TransactionType::Resolve, client, None);
+                prop_assert_eq!(manager.add_transaction(resolve), Ok(()));
+                prop_assert_eq!(snapshot(&manager, client), before);
+            }
+        }
+
+        #[test]
+        fn test_chargeback_locks_the_account(transactions in stream()) {
+            let mut manager = TransactionManager::new();
+            let mut locked = HashSet::new();
+
+            for tx in transactions {
+                let client = tx.get_client_id();
+                let is_chargeback = tx.get_transaction_type() == TransactionType::Chargeback;
+                let result = manager.add_transaction(tx);
+
+                if locked.contains(&client) {
+                    prop_assert_eq!(result, Err(ProcessingError::ClientFrozen(client)));
+                } else if is_chargeback && result.is_ok() {
+                    prop_assert!(snapshot(&manager, client).get_locked());
+                    locked.insert(client);
+                }
+            }
+        }
+
+        #[test]
+        fn test_unrelated_clients_do_not_depend_on_interleaving(
+            streams in (client_stream(1), client_stream(2), client_stream(3)),
+            picks in vec(0..3usize, 0..180),
+        ) {
+            let mut sequential = TransactionManager::new();
+            let (first, second, third) = streams;
+            for tx in first.iter().chain(second.iter()).chain(third.iter()) {
+                let _ = sequential.add_transaction(tx.clone());
+            }
+
+            // take from the picked client's stream until every stream is drained
+            let mut interleaved = TransactionManager::new();
+            let mut queues = [first.into_iter(), second.into_iter(), third.into_iter()];
+            let mut picks = picks.into_iter();
+            loop {
+                let pick = picks.next().unwrap_or(0);
+                let tx = (0..queues.len()).find_map(|offset| queues[(pick + offset) % 3].next());
+                match tx {
+                    Some(tx) => {
+                        let _ = interleaved.add_transaction(tx);
+                    }
+                    None => break,
+                }
+            }
+
+            prop_assert_eq!(sorted_snapshots(interleaved), sorted_snapshots(sequential));
+        }
+    }
+}